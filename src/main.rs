@@ -6,6 +6,7 @@ use clap::{
 };
 use colored::*;
 use log::{
+    debug,
     error,
     info,
     trace,
@@ -15,6 +16,10 @@ use log::{
 use regex::Regex;
 use serde_json::Value;
 use std::{
+    cmp::{
+        Ordering,
+        Reverse,
+    },
     collections::{
         BTreeMap as DataMap,
         BTreeSet as DataSet,
@@ -26,7 +31,15 @@ use std::{
         Read,
         Write,
     },
+    mem,
+    path::{
+        Path,
+        PathBuf,
+    },
     process,
+    ptr,
+    str::FromStr,
+    time::Duration,
 };
 
 #[cfg(test)]
@@ -34,19 +47,387 @@ mod tests;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 struct MinionResult {
+    /// The value of the inventory attribute the results are grouped by.
+    group: Option<String>,
+    step: Option<Step>,
     command: Option<String>,
     retcode: Retcode,
+    /// The old and new values of functions that are not states. The changes of
+    /// states are part of their state return.
     output: Option<String>,
+    /// The value returned by functions that are not states. The comment of
+    /// states is part of their state return.
     result: Option<String>,
     host: String,
+    state: Option<StateReturn>,
 }
 
 type MinionResults = Vec<MinionResult>;
 
+/// A step of an orchestration. Steps are ordered by the order they ran in.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct Step {
+    run_num: Option<u64>,
+    name: String,
+}
+
+impl MinionResult {
+    /// The result of a host whose values could not be interpreted.
+    fn unparsable(host: &str, error: &ResultError) -> MinionResult {
+        MinionResult {
+            host: host.to_string(),
+            retcode: Retcode::Failure(None, FailureCategory::Unparsable),
+            output: Some(error.to_string()),
+            ..MinionResult::default()
+        }
+    }
+
+    /// The key used to compress the results of multiple hosts. The fields that
+    /// are different for every host even when the results are the same are
+    /// removed and the rewrites are applied to the remaining text.
+    fn compression_key(&self, options: &CompressOptions) -> MinionResult {
+        let mut key = self.clone();
+
+        if options.substitute_minion {
+            key.substitute_minion();
+        }
+
+        key.host = String::new();
+
+        if let Some(ref mut state) = key.state {
+            state.run_num = None;
+            state.duration = None;
+            state.start_time = None;
+        }
+
+        key.rewrite(&options.rewrites);
+
+        key
+    }
+
+    /// Replace the id of the host and its short name in the result and the
+    /// output with {{minion}} and its domain in the names of other hosts with
+    /// {{domain}} so results that only differ in them can be compressed. The id
+    /// is replaced first as it contains the other two. Domains without a dot
+    /// like "local" are too common to be replaced.
+    fn substitute_minion(&mut self) {
+        let host = self.host.clone();
+        let split = host.split_once('.');
+
+        for field in &[RewriteField::Result, RewriteField::Output] {
+            for text in self.texts_mut(field) {
+                *text = replace_name(text, &host, "{{minion}}");
+
+                if let Some((short, domain)) = split {
+                    if domain.contains('.') {
+                        *text = replace_domain(text, domain, "{{domain}}");
+                    }
+                    *text = replace_name(text, short, "{{minion}}");
+                }
+            }
+        }
+    }
+
+    /// The text printed as the result which is the comment for states.
+    fn result_text(&self) -> Option<&str> {
+        match self.state {
+            Some(ref state) => state.comment.as_deref(),
+            None => self.result.as_deref(),
+        }
+    }
+
+    /// The text printed as the output which are the changes for states.
+    fn output_text(&self) -> Option<&str> {
+        match self.state {
+            Some(ref state) => state.changes.as_deref(),
+            None => self.output.as_deref(),
+        }
+    }
+
+    /// The texts of the field that the rewrites are applied to.
+    fn texts_mut(&mut self, field: &RewriteField) -> Vec<&mut String> {
+        let state = self.state.as_mut();
+
+        let texts = match *field {
+            RewriteField::Command => vec![self.command.as_mut()],
+            RewriteField::Result => vec![
+                self.result.as_mut(),
+                state.and_then(|state| state.comment.as_mut()),
+            ],
+            RewriteField::Output => vec![
+                self.output.as_mut(),
+                state.and_then(|state| state.changes.as_mut()),
+            ],
+        };
+
+        texts.into_iter().flatten().collect()
+    }
+
+    /// Apply the rewrites to the text of the result one after another and
+    /// return the values that were replaced.
+    fn rewrite(&mut self, rewrites: &[Rewrite]) -> Vec<String> {
+        let mut values = Vec::new();
+
+        for rewrite in rewrites {
+            for field in &rewrite.fields {
+                for text in self.texts_mut(field) {
+                    values.extend(
+                        rewrite
+                            .regex
+                            .find_iter(text)
+                            .map(|value| value.as_str().to_string()),
+                    );
+
+                    *text = rewrite
+                        .regex
+                        .replace_all(text, rewrite.replacement.as_str())
+                        .into_owned();
+                }
+            }
+        }
+
+        values
+    }
+
+    fn outcome(&self) -> Outcome {
+        let state = match self.state {
+            Some(ref state) => state,
+            None if self.retcode.is_success() => return Outcome::Succeeded,
+            None => return Outcome::Failed,
+        };
+
+        // states without a result only have the return code of the minion
+        match state.result {
+            Some(StateResult::Null) => Outcome::WouldChange,
+            Some(StateResult::False) => Outcome::Failed,
+            None if !self.retcode.is_success() => Outcome::Failed,
+            Some(StateResult::True) | None if state.changes.is_some() => Outcome::Changed,
+            Some(StateResult::True) | None => Outcome::Succeeded,
+        }
+    }
+}
+
+/// The outcome of a single state or of the whole return of a minion for
+/// functions that are not states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Succeeded,
+    Failed,
+    /// The state returned None as the result which it does in test runs when
+    /// it would change something.
+    WouldChange,
+    Changed,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Succeeded => write!(f, "Succeeded"),
+            Outcome::Failed => write!(f, "Failed"),
+            Outcome::WouldChange => write!(f, "Would change"),
+            Outcome::Changed => write!(f, "Changed"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "succeeded" => Ok(Outcome::Succeeded),
+            "failed" => Ok(Outcome::Failed),
+            "would_change" => Ok(Outcome::WouldChange),
+            "changed" => Ok(Outcome::Changed),
+            _ => Err(format!("unknown outcome {}", input)),
+        }
+    }
+}
+
+/// Replace all occurrences of the name in the text that are not part of a
+/// longer name. Dots are allowed next to the name so the short name of a host
+/// is replaced in its fully qualified name.
+fn replace_name(text: &str, name: &str, replacement: &str) -> String {
+    if name.is_empty() {
+        return text.to_string();
+    }
+
+    let mut output = String::new();
+    let mut last = 0;
+    for (start, _) in text.match_indices(name) {
+        let end = start + name.len();
+        if start < last {
+            continue;
+        }
+
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if before.is_some_and(is_name_char) || after.is_some_and(is_name_char) {
+            continue;
+        }
+
+        output.push_str(&text[last..start]);
+        output.push_str(replacement);
+        last = end;
+    }
+    output.push_str(&text[last..]);
+
+    output
+}
+
+/// Replace the domain where it is the end of a dotted name like in the names of
+/// other hosts. The dot in front of the domain is kept.
+fn replace_domain(text: &str, domain: &str, replacement: &str) -> String {
+    let suffix = format!(".{}", domain);
+
+    let mut output = String::new();
+    let mut last = 0;
+    for (start, _) in text.match_indices(&suffix) {
+        let end = start + suffix.len();
+        if start < last {
+            continue;
+        }
+
+        let before = text[..start].chars().next_back();
+        let mut after = text[end..].chars();
+        let next = after.next();
+        // the domain is only the beginning of a longer domain
+        let longer = next == Some('.') && after.next().is_some_and(is_name_char);
+
+        if !before.is_some_and(is_name_char) || next.is_some_and(is_name_char) || longer {
+            continue;
+        }
+
+        output.push_str(&text[last..=start]);
+        output.push_str(replacement);
+        last = end;
+    }
+    output.push_str(&text[last..]);
+
+    output
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// A replacement in the text of results before they are compressed so results
+/// that only differ in values like PIDs or timestamps end up in the same group.
+#[derive(Debug, Clone)]
+struct Rewrite {
+    regex: Regex,
+    replacement: String,
+    fields: Vec<RewriteField>,
+}
+
+#[derive(Debug, Clone)]
+enum RewriteField {
+    Command,
+    Result,
+    Output,
+}
+
+impl FromStr for RewriteField {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "command" => Ok(RewriteField::Command),
+            "result" => Ok(RewriteField::Result),
+            "output" => Ok(RewriteField::Output),
+            _ => Err(format!("unknown rewrite field {}", input)),
+        }
+    }
+}
+
+impl Rewrite {
+    fn new(regex: &str, replacement: &str, fields: &[RewriteField]) -> Self {
+        Self {
+            regex: Regex::new(regex).expect("regex of builtin rewrite is not valid"),
+            replacement: replacement.to_string(),
+            fields: fields.to_vec(),
+        }
+    }
+
+    /// Replace values that are different on every host or every run with
+    /// placeholders. IPs and durations are only replaced in the result as
+    /// versions and settings in the changes look the same.
+    fn volatile() -> Vec<Rewrite> {
+        let fields = [RewriteField::Result, RewriteField::Output];
+        let result = [RewriteField::Result];
+
+        vec![
+            Rewrite::new(
+                r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+                "<uuid>",
+                &fields,
+            ),
+            Rewrite::new(r"\b\d{20}\b", "<jid>", &fields),
+            // the format salt uses like "2017, Jul 19 11:29:04.786325"
+            Rewrite::new(
+                r"\b\d{4}, [A-Z][a-z]{2} \d{1,2} \d{2}:\d{2}:\d{2}(?:\.\d+)?",
+                "<timestamp>",
+                &fields,
+            ),
+            Rewrite::new(
+                r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+                "<timestamp>",
+                &fields,
+            ),
+            Rewrite::new(r"\b\d{2}:\d{2}:\d{2}\.\d+", "<timestamp>", &fields),
+            Rewrite::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "<ip>", &result),
+            Rewrite::new(
+                r"\b(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}\b",
+                "<ip>",
+                &result,
+            ),
+            Rewrite::new(r"(?i)\b(pid\s*[:=]?\s*)\d+\b", "${1}<pid>", &fields),
+            Rewrite::new(
+                r"\b\d+(?:\.\d+)?\s?(?:ms|milliseconds|seconds|secs?)\b",
+                "<duration>",
+                &result,
+            ),
+            Rewrite::new(r#"(?:/var)?/tmp/[^\s'"`,;:)]+"#, "<tmp>", &fields),
+        ]
+    }
+}
+
+/// The return of a single state as returned by state functions like
+/// state.highstate.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct StateReturn {
+    /// __id__
+    id: Option<String>,
+    name: Option<String>,
+    /// __sls__
+    sls: Option<String>,
+    /// __run_num__
+    run_num: Option<u64>,
+    /// None when the state return does not have a result.
+    result: Option<StateResult>,
+    comment: Option<String>,
+    /// The changes rendered as text. None when the state did not change
+    /// anything.
+    changes: Option<String>,
+    warnings: Vec<String>,
+    duration: Option<Duration>,
+    start_time: Option<String>,
+}
+
+/// The value of the result field of a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StateResult {
+    True,
+    False,
+    /// Returned in test runs when the state would change something.
+    Null,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Retcode {
     Success,
-    Failure,
+    /// The return code is None when the minion did not return one.
+    Failure(Option<u64>, FailureCategory),
 }
 
 impl Retcode {
@@ -57,7 +438,7 @@ impl Retcode {
 
 impl Default for Retcode {
     fn default() -> Retcode {
-        Retcode::Failure
+        Retcode::Failure(None, FailureCategory::NoReturnCode)
     }
 }
 
@@ -65,7 +446,123 @@ impl From<u64> for Retcode {
     fn from(input: u64) -> Self {
         match input {
             0 => Retcode::Success,
-            _ => Retcode::Failure,
+            2 => Retcode::Failure(Some(input), FailureCategory::StateFailed),
+            11 | 20 => Retcode::Failure(Some(input), FailureCategory::CompileError),
+            _ => Retcode::Failure(Some(input), FailureCategory::Error),
+        }
+    }
+}
+
+impl fmt::Display for Retcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Retcode::Success => write!(f, "Success"),
+            Retcode::Failure(Some(code), ref category) => {
+                write!(f, "Failure ({}, {})", code, category)
+            }
+            Retcode::Failure(None, ref category) => write!(f, "Failure ({})", category),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum FailureCategory {
+    Error,
+    StateFailed,
+    CompileError,
+    AlreadyRunning,
+    NotResponded,
+    NotConnected,
+    DuplicateKey,
+    /// The minion was expected but is not part of the output at all.
+    Missing,
+    NoReturnCode,
+    /// The values returned by the minion could not be interpreted.
+    Unparsable,
+}
+
+impl fmt::Display for FailureCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FailureCategory::Error => write!(f, "error"),
+            FailureCategory::StateFailed => write!(f, "state failed"),
+            FailureCategory::CompileError => write!(f, "render/compile error"),
+            FailureCategory::AlreadyRunning => write!(f, "already running"),
+            FailureCategory::NotResponded => write!(f, "did not respond"),
+            FailureCategory::NotConnected => write!(f, "not connected"),
+            FailureCategory::DuplicateKey => write!(f, "duplicate key"),
+            FailureCategory::Missing => write!(f, "missing from output"),
+            FailureCategory::NoReturnCode => write!(f, "no return code"),
+            FailureCategory::Unparsable => write!(f, "could not be interpreted"),
+        }
+    }
+}
+
+impl FromStr for FailureCategory {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "error" => Ok(FailureCategory::Error),
+            "state_failed" => Ok(FailureCategory::StateFailed),
+            "compile_error" => Ok(FailureCategory::CompileError),
+            "already_running" => Ok(FailureCategory::AlreadyRunning),
+            "not_responded" => Ok(FailureCategory::NotResponded),
+            "not_connected" => Ok(FailureCategory::NotConnected),
+            "duplicate_key" => Ok(FailureCategory::DuplicateKey),
+            "missing" => Ok(FailureCategory::Missing),
+            "no_return_code" => Ok(FailureCategory::NoReturnCode),
+            "unparsable" => Ok(FailureCategory::Unparsable),
+            _ => Err(format!("unknown failure category {}", input)),
+        }
+    }
+}
+
+/// Additional information about the salt run that is not part of the minion
+/// returns.
+#[derive(Debug, Default)]
+struct Metadata {
+    /// Lines salt printed in between the json documents like "Executing run on
+    /// [...]".
+    messages: Vec<String>,
+    /// Information about the job when the input is the output of the jobs
+    /// runner.
+    job: Option<Job>,
+}
+
+/// Job information from the output of jobs.list_job.
+#[derive(Debug, Default, PartialEq)]
+struct Job {
+    jid: Option<String>,
+    function: Option<String>,
+    arguments: Option<String>,
+    target: Option<String>,
+    target_type: Option<String>,
+    user: Option<String>,
+    start_time: Option<String>,
+    minions: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputFormat {
+    Auto,
+    Json,
+    Yaml,
+    Event,
+    Rawfile,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "auto" => Ok(InputFormat::Auto),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            "event" => Ok(InputFormat::Event),
+            "rawfile" => Ok(InputFormat::Rawfile),
+            _ => Err(format!("unknown input format {}", input)),
         }
     }
 }
@@ -74,12 +571,72 @@ impl From<u64> for Retcode {
 struct Filter {
     command: Regex,
     failed: bool,
+    outcomes: Vec<Outcome>,
     output: Regex,
     result: Regex,
     succeeded: bool,
     unchanged: bool,
 }
 
+/// The reason why the result of a host was removed by a filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Filtered {
+    /// the command does not match the command filter
+    Command,
+    /// the result failed but only succeeded results are shown
+    Failed,
+    /// the outcome is not one of the outcomes of the outcome filter
+    Outcome,
+    /// the output does not match the output filter
+    Output,
+    /// the result does not match the result filter
+    Result,
+    /// the result succeeded but only failed results are shown
+    Succeeded,
+    /// the result succeeded without changes but only changes are shown
+    Unchanged,
+}
+
+impl Filter {
+    /// Get the first filter that removes the result of a host or None if it
+    /// passes all of them. The return code and the outcome are taken from the
+    /// key and the texts are matched against the original values of the host as
+    /// the key contains the placeholders of the rewrites.
+    fn check(&self, key: &MinionResult, result: &MinionResult) -> Option<Filtered> {
+        let success = key.retcode.is_success();
+        let outcome = key.outcome();
+        let text_matches =
+            |regex: &Regex, text: Option<&str>| text.is_none_or(|text| regex.is_match(text));
+
+        if self.succeeded && !success {
+            Some(Filtered::Failed)
+        } else if self.failed && success {
+            Some(Filtered::Succeeded)
+        } else if self.unchanged
+            && key.output_text().is_none()
+            && success
+            && outcome != Outcome::WouldChange
+        {
+            Some(Filtered::Unchanged)
+        } else if !self.outcomes.is_empty() && !self.outcomes.contains(&outcome) {
+            Some(Filtered::Outcome)
+        } else if !text_matches(&self.command, result.command.as_deref()) {
+            Some(Filtered::Command)
+        } else if !text_matches(&self.result, result.result_text()) {
+            Some(Filtered::Result)
+        } else if !text_matches(&self.output, result.output_text()) {
+            Some(Filtered::Output)
+        } else {
+            None
+        }
+    }
+
+    /// Check if the result of a host passes all filters.
+    fn is_match(&self, key: &MinionResult, result: &MinionResult) -> bool {
+        self.check(key, result).is_none()
+    }
+}
+
 fn main() {
     let yaml = load_yaml!("cli.yml");
     let matches = App::from_yaml(yaml).version(crate_version!()).get_matches();
@@ -92,6 +649,8 @@ fn main() {
     }
 
     let no_save_file = matches.is_present("no_save_file");
+    let input_format = value_t!(matches, "input_format", InputFormat)
+        .expect("can not parse input format from args");
 
     let filter_failed = matches.is_present("filter_failed");
     let filter_succeeded = matches.is_present("filter_succeeded");
@@ -102,10 +661,21 @@ fn main() {
         value_t!(matches, "filter_result", Regex).expect("can not parse regex from filter_result");
     let filter_output =
         value_t!(matches, "filter_output", Regex).expect("can not parse regex from filter_output");
+    let filter_outcomes = match matches.values_of("filter_outcome") {
+        Some(values) => values
+            .map(|value| {
+                value
+                    .parse()
+                    .expect("can not parse outcome from filter_outcome")
+            })
+            .collect(),
+        None => Vec::new(),
+    };
 
     let filter = Filter {
         command: filter_command,
         failed: filter_failed,
+        outcomes: filter_outcomes,
         output: filter_output,
         result: filter_result,
         succeeded: filter_succeeded,
@@ -114,45 +684,85 @@ fn main() {
 
     trace!("filter: {:#?}", filter);
 
-    let input_data = {
-        let input = matches
-            .value_of("input")
-            .expect("can not get input file from args");
-
-        match input {
-            "-" => {
-                let mut buffer = String::new();
-                io::stdin()
-                    .read_to_string(&mut buffer)
-                    .expect("can not read from stdin");
-                buffer
+    // the patterns of the user come first so they can override the builtin ones
+    let mut patterns = match matches.value_of("failure_patterns") {
+        Some(path) => match read_failure_patterns(Path::new(path)) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("can not read failure patterns from {}: {}", path, e);
+                process::exit(1)
             }
-            _ => std::fs::read_to_string(input).expect("can not read from input file"),
-        }
+        },
+        None => Vec::new(),
+    };
+    patterns.extend(FailurePattern::builtin());
+
+    let options = ResultOptions {
+        strict: matches.is_present("strict"),
+        generic: matches.is_present("generic"),
+        patterns,
+        already_running: get_already_running(),
     };
 
-    let (host_data, failed_minions) = cleanup_input_data(input_data.as_str());
+    let (value, mut metadata, failed_minions, host_data) = match matches.value_of("input") {
+        Some(input) => {
+            let input_data = match input {
+                "-" => {
+                    let mut buffer = String::new();
+                    io::stdin()
+                        .read_to_string(&mut buffer)
+                        .expect("can not read from stdin");
+                    buffer
+                }
+                _ => std::fs::read_to_string(input).expect("can not read from input file"),
+            };
 
-    trace!("input: {}", host_data);
+            let (host_data, failed_minions) =
+                cleanup_input_data(input_data.as_str(), &options.patterns);
 
-    let value: Value = match serde_json::from_str(host_data.as_str()) {
-        Ok(v) => v,
-        Err(e) => {
-            error!(
-                "can not convert input data to value: {}\nhave you run the salt command with \
-                 --static?",
-                e
-            );
-            if !no_save_file {
-                write_save_file(host_data.as_str());
-            }
-            process::exit(1)
+            trace!("input: {}", host_data);
+
+            let jid = matches.value_of("jid");
+            let (value, metadata) = match parse_input(host_data.as_str(), input_format, jid) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("can not convert input data to value: {}", e);
+                    if !no_save_file {
+                        write_save_file(host_data.as_str());
+                    }
+                    process::exit(1)
+                }
+            };
+
+            (value, metadata, failed_minions, host_data)
+        }
+        None => {
+            let jid = matches.value_of("jid").expect("can not get jid from args");
+            let cache_dir = matches
+                .value_of("cache_dir")
+                .expect("can not get cache dir from args");
+
+            let (value, metadata) = match read_job_cache(Path::new(cache_dir), jid) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("can not read job {} from job cache: {}", jid, e);
+                    process::exit(1)
+                }
+            };
+
+            let host_data = value.to_string();
+            (value, metadata, DataMap::default(), host_data)
         }
     };
 
+    if let Some(job) = get_job(&value) {
+        metadata.job = Some(job);
+    }
+
     trace!("value: {}", value);
+    trace!("metadata: {:#?}", metadata);
 
-    let results = match get_results(&value, failed_minions) {
+    let mut results = match get_results(&value, failed_minions, &options) {
         Ok(r) => r,
         Err(e) => {
             error!("can not get results from serde value: {}", e);
@@ -165,43 +775,156 @@ fn main() {
 
     trace!("results: {:#?}", results);
 
-    let compressed = get_compressed(results);
+    if let Some(path) = matches.value_of("expected_minions") {
+        match read_expected_minions(Path::new(path)) {
+            Ok(expected) => add_missing_minions(&mut results, &expected),
+            Err(e) => {
+                error!("can not read expected minions from {}: {}", path, e);
+                process::exit(1)
+            }
+        }
+    }
+
+    let unparsable = results
+        .iter()
+        .any(|result| result.retcode == Retcode::Failure(None, FailureCategory::Unparsable));
+    if unparsable && !no_save_file {
+        write_save_file(host_data.as_str());
+    }
+
+    let inventory = match matches.value_of("inventory") {
+        Some(path) => match read_inventory(Path::new(path)) {
+            Ok(i) => i,
+            Err(e) => {
+                error!("can not read inventory from {}: {}", path, e);
+                process::exit(1)
+            }
+        },
+        None => Inventory::new(),
+    };
+
+    if let Some(attribute) = matches.value_of("group_by") {
+        for result in &mut results {
+            let value = inventory
+                .get(&result.host)
+                .and_then(|attributes| attributes.get(attribute))
+                .map_or("unknown", String::as_str);
+
+            result.group = Some(format!("{}={}", attribute, value));
+        }
+    }
+
+    // the rules of the user come first so they can match values before they
+    // are replaced with placeholders
+    let mut rewrites = match matches.value_of("rewrites") {
+        Some(path) => match read_rewrites(Path::new(path)) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("can not read rewrites from {}: {}", path, e);
+                process::exit(1)
+            }
+        },
+        None => Vec::new(),
+    };
+
+    // the values in the return of generic functions are what is compared so
+    // they are not replaced
+    if !matches.is_present("no_normalize") && !options.generic {
+        rewrites.extend(Rewrite::volatile());
+    }
+
+    let compress_options = CompressOptions {
+        rewrites,
+        substitute_minion: matches.is_present("substitute_minion"),
+    };
+
+    let compressed = get_compressed(results, &compress_options);
     trace!("compressed: {:#?}", compressed);
 
-    print_compressed(compressed, &filter);
+    let cluster = if matches.is_present("cluster") {
+        Some(value_t!(matches, "similarity", f64).expect("can not parse similarity from args"))
+    } else {
+        None
+    };
+
+    let print_options = PrintOptions {
+        details: matches.is_present("details"),
+        rewrites: compress_options.rewrites,
+        cluster,
+        hosts_display: value_t!(matches, "hosts_display", HostsDisplay)
+            .expect("can not parse hosts display from args"),
+        inventory,
+        annotate: match matches.values_of("annotate") {
+            Some(values) => values.map(ToString::to_string).collect(),
+            None => Vec::new(),
+        },
+        order: value_t!(matches, "order", Order).expect("can not parse order from args"),
+    };
+
+    print_metadata(&metadata);
+    if matches.is_present("fingerprint") {
+        print_fingerprints(compressed, &filter, &print_options);
+    } else if options.generic {
+        print_generic(compressed, &filter, &print_options);
+    } else {
+        print_compressed(compressed, &filter, &print_options);
+    }
+}
+
+/// Options for getting the results from the values returned by the minions.
+#[derive(Debug)]
+struct ResultOptions {
+    /// Abort on the first host that can not be interpreted instead of adding a
+    /// result for it with the failure category Unparsable.
+    strict: bool,
+    /// Create a result for every value in the return instead of expecting
+    /// states. Used for functions like grains.items or pkg.list_pkgs.
+    generic: bool,
+    /// Patterns for the messages salt returns for minions that did not return
+    /// anything.
+    patterns: Vec<FailurePattern>,
+    /// The message salt returns when the function is already running on the
+    /// minion.
+    already_running: Regex,
+}
+
+impl Default for ResultOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            generic: false,
+            patterns: FailurePattern::builtin(),
+            already_running: get_already_running(),
+        }
+    }
 }
 
 #[derive(Debug)]
 enum ResultError {
-    ConvertDiffToString,
-    ConvertValueToString,
     ReturnCodeNotNumber,
-    RetValueIsNull,
-    RetValueIsNumber,
     ValueNotAnObject,
-    OldIsNotAString,
-    NewIsNotAString,
+    InvalidStateField(&'static str),
 }
 
 impl fmt::Display for ResultError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ResultError::ConvertDiffToString => write!(f, "can not convert diff to string"),
-            ResultError::ConvertValueToString => write!(f, "can not convert value to string"),
             ResultError::ReturnCodeNotNumber => write!(f, "returncode is not a number"),
-            ResultError::RetValueIsNull => write!(f, "ret value is null"),
-            ResultError::RetValueIsNumber => write!(f, "ret value is number"),
             ResultError::ValueNotAnObject => write!(f, "value it not an object"),
-            ResultError::OldIsNotAString => write!(f, "old is not a string"),
-            ResultError::NewIsNotAString => write!(f, "new is not a string"),
+            ResultError::InvalidStateField(ref field) => {
+                write!(f, "state field {} has an invalid value", field)
+            }
         }
     }
 }
 
 fn get_results(
     value: &Value,
-    failed_minions: DataMap<String, &str>,
+    failed_minions: DataMap<String, (FailureCategory, String)>,
+    options: &ResultOptions,
 ) -> Result<MinionResults, ResultError> {
+    let value = get_minions(value);
+
     if !value.is_object() {
         return Err(ResultError::ValueNotAnObject);
     }
@@ -209,152 +932,298 @@ fn get_results(
     let mut results: MinionResults = Vec::new();
 
     for (host, values) in value.as_object().unwrap().iter() {
-        trace!("host: {:#?}", host);
-        trace!("values: {:#?}", values);
+        // the json outputter returns the message for minions that did not
+        // return as a string
+        if let Some((category, message)) = values
+            .as_str()
+            .and_then(|line| FailurePattern::find(&options.patterns, line))
+        {
+            results.push(MinionResult {
+                host: host.clone(),
+                retcode: Retcode::Failure(None, category),
+                output: Some(message),
+                ..MinionResult::default()
+            });
+            continue;
+        }
 
-        let retcode: Retcode = match values.get("retcode") {
-            Some(o) => match o.as_u64() {
-                Some(v) => v.into(),
-                None => return Err(ResultError::ReturnCodeNotNumber),
-            },
-            None => {
-                warn!("host {} does not have a return code", host);
-                Retcode::Failure
+        if let Some(steps) = get_orchestrate_steps(values) {
+            results.extend(get_orchestrate_results(host, steps, options)?);
+            continue;
+        }
+
+        match get_host_results(host, values, options) {
+            Ok(host_results) => results.extend(host_results),
+            Err(e) if !options.strict => {
+                warn!("can not get results of host {}: {}", host, e);
+                results.push(MinionResult::unparsable(host, &e));
             }
-        };
+            Err(e) => return Err(e),
+        }
+    }
 
-        let ret = match values.get("ret") {
-            Some(r) => r,
-            None => values,
-        };
+    for (host, (category, message)) in failed_minions {
+        results.push(MinionResult {
+            host,
+            retcode: Retcode::Failure(None, category),
+            output: Some(message),
+            ..MinionResult::default()
+        });
+    }
 
-        match *ret {
-            Value::Null => return Err(ResultError::RetValueIsNull),
-            Value::Bool(r) => {
-                let result = Some(r.to_string());
+    Ok(results)
+}
 
-                results.push(MinionResult {
-                    host: host.clone(),
-                    result,
-                    retcode,
-                    ..MinionResult::default()
-                });
-            }
-            Value::Number(_) => return Err(ResultError::RetValueIsNumber),
-            Value::String(ref r) => {
-                results.push(MinionResult {
-                    host: host.clone(),
-                    result: Some(r.clone()),
-                    retcode,
-                    ..MinionResult::default()
-                });
-            }
+/// Get the steps of an orchestration if the values are the return of
+/// state.orchestrate. The steps that ran salt.state or salt.function have the
+/// returns of the targeted minions under "ret" in their changes.
+fn get_orchestrate_steps(values: &Value) -> Option<&serde_json::Map<String, Value>> {
+    let steps = values.as_object()?;
+
+    let is_orchestrate = steps.iter().any(|(key, step)| {
+        key.starts_with("salt_|-")
+            && step
+                .get("changes")
+                .and_then(|changes| changes.get("ret"))
+                .is_some_and(Value::is_object)
+    });
+
+    if is_orchestrate {
+        Some(steps)
+    } else {
+        None
+    }
+}
 
-            Value::Array(ref r) => {
-                let values: Vec<_> = r
-                    .iter()
-                    .map(|v| {
-                        v.as_str()
-                            .expect("can not convert the array value to a string")
-                    })
-                    .collect();
+/// Get the results of every minion in every step of an orchestration. The step
+/// is added to the results so the minions of each step are compressed
+/// separately. Steps that did not run on minions are added as results of the
+/// master.
+fn get_orchestrate_results(
+    master: &str,
+    steps: &serde_json::Map<String, Value>,
+    options: &ResultOptions,
+) -> Result<MinionResults, ResultError> {
+    let mut results: MinionResults = Vec::new();
 
-                results.push(MinionResult {
-                    host: host.clone(),
-                    result: Some(values.join("\n").to_string()),
-                    retcode,
-                    ..MinionResult::default()
-                });
+    for (key, step) in steps {
+        let name = step
+            .get("__id__")
+            .and_then(Value::as_str)
+            .unwrap_or(key)
+            .to_string();
+        let run_num = step.get("__run_num__").and_then(Value::as_u64);
+
+        let step_failed = step.get("result").and_then(Value::as_bool) == Some(false);
+
+        let minions = step
+            .get("changes")
+            .and_then(|changes| changes.get("ret"))
+            .and_then(Value::as_object);
+
+        let mut step_results = match minions {
+            Some(minions) => {
+                let mut step_results: MinionResults = Vec::new();
+
+                for (host, ret) in minions {
+                    // the return codes of the minions are not part of the
+                    // orchestration so they are derived from the states or the
+                    // result of the step
+                    let retcode = match get_states_retcode(ret) {
+                        Some(retcode) => retcode,
+                        None if step_failed => Retcode::Failure(None, FailureCategory::Error),
+                        None => Retcode::Success,
+                    };
+
+                    match get_return_results(host, retcode, None, ret, options) {
+                        Ok(host_results) => step_results.extend(host_results),
+                        Err(e) if !options.strict => {
+                            warn!(
+                                "can not get results of host {} in step {}: {}",
+                                host, name, e
+                            );
+                            step_results.push(MinionResult::unparsable(host, &e));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                step_results
             }
-            Value::Object(ref r) => {
-                if r.is_empty() {
-                    results.push(MinionResult {
-                        host: host.clone(),
-                        retcode: retcode.clone(),
-                        ..MinionResult::default()
-                    });
+            None => {
+                let retcode = if step_failed {
+                    Retcode::Failure(None, FailureCategory::StateFailed)
+                } else {
+                    Retcode::Success
+                };
+
+                let mut ret = serde_json::Map::new();
+                ret.insert(key.clone(), step.clone());
+
+                match get_return_results(master, retcode, None, &Value::Object(ret), options) {
+                    Ok(master_results) => master_results,
+                    Err(e) if !options.strict => {
+                        warn!("can not get results of step {}: {}", name, e);
+                        vec![MinionResult::unparsable(master, &e)]
+                    }
+                    Err(e) => return Err(e),
                 }
+            }
+        };
 
-                for (command, command_result) in r.iter() {
-                    trace!("command: {:#?}", command);
-                    trace!("command_result: {:#?}", command_result);
+        for result in &mut step_results {
+            result.step = Some(Step {
+                run_num,
+                name: name.clone(),
+            });
+        }
 
-                    let result = match command_result.get("comment") {
-                        Some(r) => match r.as_str() {
-                            Some(s) => Some(s.to_string()),
-                            None => return Err(ResultError::ConvertValueToString),
-                        },
-                        None => None,
-                    };
+        results.extend(step_results);
+    }
 
-                    let old = match command_result.get("old") {
-                        Some(r) => match r.as_str() {
-                            Some(s) => Some(s.to_string()),
-                            None => return Err(ResultError::OldIsNotAString),
-                        },
-                        None => None,
-                    };
+    Ok(results)
+}
 
-                    let new = match command_result.get("new") {
-                        Some(r) => match r.as_str() {
-                            Some(s) => Some(s.to_string()),
-                            None => return Err(ResultError::NewIsNotAString),
-                        },
-                        None => None,
-                    };
+/// Add a result for every expected minion that has no result at all. Like the
+/// failed minions found in the input they get a failure without a return code.
+fn add_missing_minions(results: &mut MinionResults, expected: &DataSet<String>) {
+    let hosts: DataSet<&str> = results.iter().map(|result| result.host.as_str()).collect();
 
-                    let output = match command_result.get("changes") {
-                        Some(r) => match r.get("diff") {
-                            Some(d) => match d.as_str() {
-                                Some(i) => Some(i.to_string()),
-                                None => return Err(ResultError::ConvertDiffToString),
-                            },
-                            None => None,
-                        },
-                        None => None,
-                    };
+    let missing: Vec<&String> = expected
+        .iter()
+        .filter(|host| !hosts.contains(host.as_str()))
+        .collect();
 
-                    let output = if old.is_some() {
-                        match output {
-                            Some(mut s) => {
-                                s.push_str(format!("Old: {}\n", old.unwrap()).as_str());
-                                Some(s)
-                            }
-                            None => Some(format!("Old: {}\n", old.unwrap())),
-                        }
-                    } else {
-                        output
-                    };
+    for host in missing {
+        results.push(MinionResult {
+            host: host.clone(),
+            retcode: Retcode::Failure(None, FailureCategory::Missing),
+            output: Some("Minion is missing from the output.".to_string()),
+            ..MinionResult::default()
+        });
+    }
+}
 
-                    let output = if new.is_some() {
-                        match output {
-                            Some(mut s) => {
-                                s.push_str(format!("New: {}\n", new.unwrap()).as_str());
-                                Some(s)
-                            }
-                            None => Some(format!("New: {}\n", new.unwrap())),
-                        }
-                    } else {
-                        output
-                    };
+/// Get the results of a single host from the values it returned.
+fn get_host_results(
+    host: &str,
+    values: &Value,
+    options: &ResultOptions,
+) -> Result<MinionResults, ResultError> {
+    trace!("host: {:#?}", host);
+    trace!("values: {:#?}", values);
+
+    let ssh = is_ssh_return(values);
+
+    // salt-ssh returns the output of the minion as a json string when it could
+    // not be deserialized
+    let ssh_return = if ssh {
+        match values.get("return") {
+            Some(Value::String(r)) => parse_ssh_stdout(r),
+            Some(_) => None,
+            None => values
+                .get("stdout")
+                .and_then(Value::as_str)
+                .and_then(parse_ssh_stdout),
+        }
+    } else {
+        None
+    };
 
-                    results.push(MinionResult {
-                        command: Some(command.to_string()),
-                        host: host.clone(),
-                        output,
-                        result,
-                        retcode: retcode.clone(),
-                    });
+    // the jobs runner, the job cache and salt-ssh use return instead of ret.
+    // The job cache of older versions of salt only contains the return.
+    let only_return = values.as_object().is_some_and(|values| values.len() == 1);
+    let ret = match ssh_return {
+        Some(ref r) => r,
+        None if ssh => values.get("return").unwrap_or(&Value::Null),
+        None => match values.get("ret") {
+            Some(r) => r,
+            None => match values.get("return") {
+                Some(r)
+                    if values.get("retcode").is_some()
+                        || values.get("success").is_some()
+                        || only_return =>
+                {
+                    r
                 }
-            }
-        };
+                _ => values,
+            },
+        },
+    };
+
+    // without a separate return the values are the return of the function
+    // itself like the one of cmd.run_all which contains the exit code of the
+    // command
+    let retcode_field = if ptr::eq(ret, values) {
+        None
+    } else {
+        values.get("retcode")
+    };
+
+    let retcode: Retcode = match retcode_field {
+        Some(o) => match o.as_u64() {
+            Some(v) => v.into(),
+            None => return Err(ResultError::ReturnCodeNotNumber),
+        },
+        None => match values.get("success").and_then(Value::as_bool) {
+            Some(true) => Retcode::Success,
+            Some(false) => Retcode::Failure(None, FailureCategory::Error),
+            // jobs.lookup_jid only returns the states without a return code
+            None => match get_states_retcode(ret) {
+                Some(retcode) => retcode,
+                None => {
+                    warn!("host {} does not have a return code", host);
+                    Retcode::Failure(None, FailureCategory::NoReturnCode)
+                }
+            },
+        },
+    };
+
+    let retcode = match retcode {
+        Retcode::Failure(code, _) if is_already_running(ret, &options.already_running) => {
+            Retcode::Failure(code, FailureCategory::AlreadyRunning)
+        }
+        _ => retcode,
+    };
+
+    // only set for the return data of jobs as sent by the minions
+    let function = values.get("fun").and_then(Value::as_str);
+
+    if !ssh {
+        return get_return_results(host, retcode, function, ret, options);
+    }
+
+    // errors of salt-ssh itself like failed logins are only in stderr
+    let text = |field: &str| {
+        values
+            .get(field)
+            .and_then(Value::as_str)
+            .filter(|text| !text.trim().is_empty())
+            .map(ToString::to_string)
+    };
+    let stderr = text("stderr");
+
+    // without a return there is nothing to interpret so stdout and stderr are
+    // shown as they are
+    if ret.is_null() {
+        return Ok(vec![MinionResult {
+            command: function.map(ToString::to_string),
+            host: host.to_string(),
+            output: stderr,
+            result: text("stdout"),
+            retcode,
+            ..MinionResult::default()
+        }]);
     }
 
-    for (host, message) in failed_minions {
+    let mut results = get_return_results(host, retcode.clone(), function, ret, options)?;
+
+    if let Some(stderr) = stderr {
         results.push(MinionResult {
-            host,
-            retcode: Retcode::Failure,
-            output: Some(message.into()),
+            command: Some("stderr".to_string()),
+            host: host.to_string(),
+            output: Some(stderr),
+            retcode,
             ..MinionResult::default()
         });
     }
@@ -362,209 +1231,1565 @@ fn get_results(
     Ok(results)
 }
 
-fn get_compressed(results: MinionResults) -> DataMap<MinionResult, Vec<String>> {
-    // compress output by changeing the hostname to the same value for all results
-    // and then just
-    // adding all hosts with that value to the map.
-    let mut compressed: DataMap<MinionResult, Vec<String>> = DataMap::new();
-    for result in results {
-        let mut result_no_host = result.clone();
-        result_no_host.host = String::new();
+/// Check if the values have the shape of a host returned by salt-ssh. The
+/// return of cmd.run_all has the same fields but always contains the pid.
+fn is_ssh_return(values: &Value) -> bool {
+    let values = match values.as_object() {
+        Some(values) => values,
+        None => return false,
+    };
 
-        compressed
-            .entry(result_no_host)
-            .or_insert_with(Vec::new)
-            .push(result.host);
+    if values.contains_key("pid")
+        || !values.contains_key("retcode")
+        || !(values.contains_key("stdout") || values.contains_key("stderr"))
+    {
+        return false;
     }
 
-    compressed
+    // the shim prints the return of the minion as {"local": ...} to stdout
+    let shim_stdout = values
+        .get("stdout")
+        .and_then(Value::as_str)
+        .and_then(|stdout| serde_json::from_str::<Value>(stdout.trim()).ok())
+        .is_some_and(|stdout| stdout.get("local").is_some());
+
+    // hosts salt-ssh could not connect to only have the output of ssh
+    let only_ssh_output = values
+        .keys()
+        .all(|key| matches!(key.as_str(), "retcode" | "stderr" | "stdout"));
+
+    values.contains_key("return") || shim_stdout || only_ssh_output
 }
 
-fn print_compressed(compressed: DataMap<MinionResult, Vec<String>>, filter: &Filter) {
-    let mut succeeded_hosts = DataSet::default();
-    let mut failed_hosts = DataSet::default();
+/// Parse the json the salt-ssh shim printed to stdout. The return of the minion
+/// is wrapped as {"local": {"return": ...}}. Returns None if stdout does not
+/// contain a json object.
+fn parse_ssh_stdout(stdout: &str) -> Option<Value> {
+    let value: Value = serde_json::from_str(stdout.trim()).ok()?;
 
-    let mut filter_command = DataSet::default();
-    let mut filter_failed = DataSet::default();
-    let mut filter_result = DataSet::default();
-    let mut filter_output = DataSet::default();
-    let mut filter_succeeded = DataSet::default();
-    let mut filter_unchanged = 0;
+    match value.get("local") {
+        Some(local) => Some(local.get("return").unwrap_or(local).clone()),
+        None if value.is_object() => Some(value),
+        None => None,
+    }
+}
 
-    for (result, hosts) in compressed {
-        // continue if we only want to print out changes and there are none and the
-        // command was a
-        // success
-        // TODO: make this a filter of the map
-        if filter.succeeded && !result.retcode.is_success() {
-            for host in hosts {
-                filter_failed.insert(host);
-            }
-            continue;
-        }
+/// Get the results of a single host from the return of the function that ran on
+/// it.
+fn get_return_results(
+    host: &str,
+    retcode: Retcode,
+    function: Option<&str>,
+    ret: &Value,
+    options: &ResultOptions,
+) -> Result<MinionResults, ResultError> {
+    let mut results: MinionResults = Vec::new();
 
-        if filter.failed && result.retcode.is_success() {
-            for host in hosts {
-                filter_succeeded.insert(host);
+    if options.generic {
+        get_generic_results(host, &retcode, ret, None, &mut results);
+        return Ok(results);
+    }
+
+    match *ret {
+        Value::Object(ref r) => {
+            if r.is_empty() {
+                results.push(MinionResult {
+                    host: host.to_string(),
+                    retcode: retcode.clone(),
+                    ..MinionResult::default()
+                });
             }
-            continue;
-        }
 
-        if filter.unchanged && result.output.is_none() && result.retcode.is_success() {
-            filter_unchanged += 1;
-            continue;
-        }
+            for (command, command_result) in r.iter() {
+                trace!("command: {:#?}", command);
+                trace!("command_result: {:#?}", command_result);
 
-        if result.command.is_some()
-            && !filter
-                .command
-                .is_match(result.command.clone().unwrap().as_str())
-        {
-            for host in hosts {
-                filter_command.insert(host);
+                let state = get_state_return(command_result)?;
+
+                // the comment and the changes of states are part of their state
+                // return
+                if state.is_some() {
+                    results.push(MinionResult {
+                        command: Some(command.to_string()),
+                        host: host.to_string(),
+                        retcode: retcode.clone(),
+                        state,
+                        ..MinionResult::default()
+                    });
+                    continue;
+                }
+
+                let old = command_result.get("old").map(render_result);
+                let new = command_result.get("new").map(render_result);
+
+                // values of functions that do not return states
+                if old.is_none() && new.is_none() {
+                    results.push(MinionResult {
+                        command: Some(command.to_string()),
+                        host: host.to_string(),
+                        result: Some(render_result(command_result)),
+                        retcode: retcode.clone(),
+                        ..MinionResult::default()
+                    });
+                    continue;
+                }
+
+                let output = old.map(|old| format!("Old: {}\n", old));
+
+                let output = match new {
+                    Some(new) => match output {
+                        Some(mut s) => {
+                            s.push_str(format!("New: {}\n", new).as_str());
+                            Some(s)
+                        }
+                        None => Some(format!("New: {}\n", new)),
+                    },
+                    None => output,
+                };
+
+                results.push(MinionResult {
+                    command: Some(command.to_string()),
+                    host: host.to_string(),
+                    output,
+                    retcode: retcode.clone(),
+                    ..MinionResult::default()
+                });
             }
-            continue;
         }
+        ref other => {
+            results.push(MinionResult {
+                command: function.map(ToString::to_string),
+                host: host.to_string(),
+                result: Some(render_result(other)),
+                retcode,
+                ..MinionResult::default()
+            });
+        }
+    };
 
-        if result.result.is_some()
-            && !filter
-                .result
-                .is_match(result.result.clone().unwrap().as_str())
-        {
-            for host in hosts {
-                filter_result.insert(host);
+    Ok(results)
+}
+
+/// Add a result for every value in the return with the path to the value as the
+/// command so hosts can be grouped by the value at each path. Objects and
+/// arrays that contain objects or arrays are descended into. The parts of the
+/// path are joined with ":" like salt does for grains.get.
+fn get_generic_results(
+    host: &str,
+    retcode: &Retcode,
+    value: &Value,
+    path: Option<&str>,
+    results: &mut MinionResults,
+) {
+    let join = |key: &str| match path {
+        Some(path) => format!("{}:{}", path, key),
+        None => key.to_string(),
+    };
+
+    match *value {
+        Value::Object(ref object) if !object.is_empty() => {
+            for (key, value) in object {
+                get_generic_results(host, retcode, value, Some(&join(key)), results);
             }
-            continue;
         }
-
-        if result.output.is_some()
-            && !filter
-                .output
-                .is_match(result.output.clone().unwrap().as_str())
+        Value::Array(ref array)
+            if array
+                .iter()
+                .any(|value| value.is_object() || value.is_array()) =>
         {
-            for host in hosts {
-                filter_output.insert(host);
+            for (index, value) in array.iter().enumerate() {
+                get_generic_results(
+                    host,
+                    retcode,
+                    value,
+                    Some(&join(&index.to_string())),
+                    results,
+                );
             }
-            continue;
         }
+        ref value => results.push(MinionResult {
+            command: path.map(ToString::to_string),
+            host: host.to_string(),
+            result: Some(render_result(value)),
+            retcode: retcode.clone(),
+            ..MinionResult::default()
+        }),
+    }
+}
 
-        println!();
-        println!("{}", "----------".bold());
-        println!();
-
-        // state, command info
-        {
-            if result.command.is_some() {
-                println!("{}", "------".purple());
+/// Render the changes of a state. A diff is used as it is and all other changes
+/// are rendered as nested keys and values in a deterministic order so the
+/// changes of multiple hosts can be compressed. Diffs that are not text are
+/// rendered like the other changes.
+fn render_changes(changes: &Value) -> Option<String> {
+    let mut output = String::new();
+
+    match *changes {
+        Value::Null => {}
+        Value::Object(ref changes) => {
+            let mut rest = changes.clone();
+
+            if let Some(Value::String(diff)) = changes.get("diff") {
+                output.push_str(diff.as_str());
+                rest.remove("diff");
+            }
 
-                if result.command.is_some() {
-                    println!(
-                        "{}",
-                        format!("COMMAND: {}", result.command.clone().unwrap()).purple()
-                    );
+            if !rest.is_empty() {
+                if !output.is_empty() && !output.ends_with('\n') {
+                    output.push('\n');
                 }
-
-                println!("{}\n", "------".purple());
+                render_value(&Value::Object(rest), 0, &mut output);
             }
         }
+        ref other => render_value(other, 0, &mut output),
+    }
 
-        // hosts
-        {
-            println!("{}", "------".cyan());
-            println!("{}{}", "HOSTS: ".cyan(), hosts.join(", ").as_str());
-            println!("{}\n", "------".cyan());
-        }
+    if output.is_empty() {
+        None
+    } else {
+        Some(output)
+    }
+}
 
-        // output
+/// Render a value returned by a minion as text. Arrays of single line values
+/// are rendered as one value per line and everything else like the changes of
+/// states.
+fn render_result(value: &Value) -> String {
+    if let Some(scalar) = render_scalar(value) {
+        return scalar;
+    }
+
+    match *value {
+        Value::String(ref s) => s.clone(),
+        Value::Array(ref a)
+            if a.iter()
+                .all(|v| v.is_string() || render_scalar(v).is_some()) =>
         {
-            println!("{}", "------".yellow());
+            a.iter().map(render_result).collect::<Vec<_>>().join("\n")
+        }
+        ref other => {
+            let mut output = String::new();
+            render_value(other, 0, &mut output);
+            output.trim_end_matches('\n').to_string()
+        }
+    }
+}
 
-            match result.retcode {
-                Retcode::Success => {
-                    for host in hosts {
-                        succeeded_hosts.insert(host);
+/// Render a value as indented lines. Objects are rendered as "key: value" and
+/// arrays as "- value" with nested values on the following lines.
+fn render_value(value: &Value, indent: usize, output: &mut String) {
+    let prefix = " ".repeat(indent);
+
+    match *value {
+        Value::Object(ref object) => {
+            for (key, value) in object {
+                match render_scalar(value) {
+                    Some(scalar) => output.push_str(&format!("{}{}: {}\n", prefix, key, scalar)),
+                    None => {
+                        output.push_str(&format!("{}{}:\n", prefix, key));
+                        render_value(value, indent + 2, output);
                     }
-                    println!("{}{}", "RETURN CODE: ".yellow(), "Success".green())
                 }
-                Retcode::Failure => {
-                    for host in hosts {
-                        failed_hosts.insert(host);
+            }
+        }
+        Value::Array(ref array) => {
+            for value in array {
+                match render_scalar(value) {
+                    Some(scalar) => output.push_str(&format!("{}- {}\n", prefix, scalar)),
+                    None => {
+                        output.push_str(&format!("{}-\n", prefix));
+                        render_value(value, indent + 2, output);
                     }
-                    println!("{}{}", "RETURN CODE: ".yellow(), "Failure".red())
                 }
             }
-
-            if result.result.is_some() {
-                println!("{}", "RESULT:".yellow());
-                println!("{}\n", result.result.unwrap());
+        }
+        Value::String(ref s) => {
+            for line in s.lines() {
+                output.push_str(&format!("{}{}\n", prefix, line));
             }
+        }
+        ref other => output.push_str(&format!("{}{}\n", prefix, other)),
+    }
+}
 
-            println!("{}", "OUTPUT:".yellow());
-            if result.output.is_some() {
-                for line in result.output.unwrap().lines() {
-                    if line.starts_with('-') {
-                        println!("{}", line.red());
-                        continue;
-                    }
+/// Render values that fit on a single line. Returns None for everything else.
+fn render_scalar(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) if !s.contains('\n') => Some(s.clone()),
+        Value::String(_) => None,
+        Value::Array(ref a) if a.is_empty() => Some("[]".to_string()),
+        Value::Array(_) => None,
+        Value::Object(ref o) if o.is_empty() => Some("{}".to_string()),
+        Value::Object(_) => None,
+        ref other => Some(other.to_string()),
+    }
+}
 
-                    if line.starts_with('+') {
-                        println!("{}", line.green());
-                        continue;
-                    }
+/// The message salt returns when the function is already running on the
+/// minion.
+fn get_already_running() -> Regex {
+    Regex::new(r#"^The function "\S+" is running as PID \d+"#)
+        .expect("regex for catching already running functions is not valid")
+}
 
-                    println!("{}", line);
-                }
-            } else {
-                println!("No changes");
-            }
-            println!("{}", "------".yellow());
-        }
+/// Check if the return is the message salt returns when the function is
+/// already running on the minion.
+fn is_already_running(ret: &Value, already_running: &Regex) -> bool {
+    match *ret {
+        Value::String(ref s) => already_running.is_match(s),
+        Value::Array(ref a) => a
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|s| already_running.is_match(s)),
+        _ => false,
     }
+}
 
-    println!();
+/// Check if the value looks like the return of a state.
+fn is_state_return(value: &Value) -> bool {
+    let fields = [
+        "__id__",
+        "__run_num__",
+        "__sls__",
+        "changes",
+        "comment",
+        "result",
+    ];
+
+    fields.iter().any(|field| value.get(field).is_some())
+}
 
-    print_filter_statistics("command", filter_command.len());
-    print_filter_statistics("result", filter_result.len());
-    print_filter_statistics("output", filter_output.len());
-    print_filter_statistics("failed", filter_failed.len());
-    print_filter_statistics("succeeded", filter_succeeded.len());
-    print_filter_statistics("changed", filter_unchanged);
+/// Derive the return code of a minion from the results of its states for
+/// outputs that do not contain the return codes like orchestrations or
+/// jobs.lookup_jid. Returns None if the return does not contain states.
+fn get_states_retcode(ret: &Value) -> Option<Retcode> {
+    let states = ret
+        .as_object()
+        .filter(|states| states.values().any(is_state_return))?;
 
-    info!(
-        "succeeded host{}: {}",
-        if succeeded_hosts.len() > 1 || succeeded_hosts.is_empty() {
-            "s"
-        } else {
-            ""
-        },
-        succeeded_hosts.len()
-    );
-    info!(
-        "failed host{}: {}",
-        if failed_hosts.len() > 1 || failed_hosts.is_empty() {
-            "s"
-        } else {
-            ""
-        },
-        failed_hosts.len()
-    );
-}
+    let failed = states
+        .values()
+        .any(|state| state.get("result").and_then(Value::as_bool) == Some(false));
 
-fn print_filter_statistics(stats: &str, count: usize) {
-    info!(
-        "filtered {} state{}: {}",
-        stats,
-        if count > 1 || count == 0 { "s" } else { "" },
-        count
-    );
+    if failed {
+        Some(Retcode::Failure(None, FailureCategory::StateFailed))
+    } else {
+        Some(Retcode::Success)
+    }
 }
 
-fn write_save_file(host_data: &str) {
-    let save_filename = format!(
-        "/tmp/salt-compressor_{}.json",
-        chrono::Utc::now().timestamp()
-    );
-    let mut save_file = File::create(save_filename.clone()).expect("can not create save_file");
+/// Get the return of a single state. Returns None if the value does not look
+/// like the return of a state.
+fn get_state_return(value: &Value) -> Result<Option<StateReturn>, ResultError> {
+    if !is_state_return(value) {
+        return Ok(None);
+    }
+
+    let string = |field: &'static str| match value.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(ResultError::InvalidStateField(field)),
+    };
+
+    let result = match value.get("result") {
+        None => None,
+        Some(Value::Null) => Some(StateResult::Null),
+        Some(Value::Bool(true)) => Some(StateResult::True),
+        Some(Value::Bool(false)) => Some(StateResult::False),
+        Some(_) => return Err(ResultError::InvalidStateField("result")),
+    };
+
+    let run_num = match value.get("__run_num__") {
+        None => None,
+        Some(r) => match r.as_u64() {
+            Some(r) => Some(r),
+            None => return Err(ResultError::InvalidStateField("__run_num__")),
+        },
+    };
+
+    let warnings = match value.get("warnings") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(a)) => a.iter().map(value_to_string).collect(),
+        Some(_) => return Err(ResultError::InvalidStateField("warnings")),
+    };
+
+    // the duration is a number of milliseconds or a string like "1.234 ms" in
+    // older versions of salt
+    let duration = match value.get("duration") {
+        None | Some(Value::Null) => None,
+        Some(d) => {
+            let milliseconds = match d {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim_end_matches("ms").trim().parse().ok(),
+                _ => None,
+            };
+
+            match milliseconds {
+                Some(ms) if ms.is_finite() && ms >= 0.0 => {
+                    Some(Duration::from_secs_f64(ms / 1000.0))
+                }
+                _ => return Err(ResultError::InvalidStateField("duration")),
+            }
+        }
+    };
+
+    Ok(Some(StateReturn {
+        id: string("__id__")?,
+        name: string("name")?,
+        sls: string("__sls__")?,
+        run_num,
+        result,
+        comment: match value.get("comment") {
+            None | Some(Value::Null) => None,
+            Some(comment) => Some(render_result(comment)),
+        },
+        changes: value.get("changes").and_then(render_changes),
+        warnings,
+        duration,
+        start_time: string("start_time")?,
+    }))
+}
+
+/// Get the object that contains the minion returns. The output of
+/// jobs.list_job has them under "Result" and the output of jobs.lookup_jid
+/// under "data" next to the name of the outputter.
+fn get_minions(value: &Value) -> &Value {
+    if is_list_job(value) {
+        if let Some(result) = value.get("Result") {
+            return result;
+        }
+    }
+
+    if value.get("outputter").is_some() {
+        if let Some(data) = value.get("data") {
+            return data;
+        }
+    }
+
+    value
+}
+
+fn is_list_job(value: &Value) -> bool {
+    value.get("Result").is_some()
+        && (value.get("jid").is_some()
+            || value.get("Function").is_some()
+            || value.get("StartTime").is_some())
+}
+
+/// Get the job information from the output of jobs.list_job.
+fn get_job(value: &Value) -> Option<Job> {
+    if !is_list_job(value) {
+        return None;
+    }
+
+    let field = |name: &str| value.get(name).map(value_to_string);
+
+    Some(Job {
+        jid: field("jid"),
+        function: field("Function"),
+        arguments: value.get("Arguments").map(|arguments| match arguments {
+            Value::Array(a) => a.iter().map(value_to_string).collect::<Vec<_>>().join(", "),
+            other => value_to_string(other),
+        }),
+        target: field("Target"),
+        target_type: field("Target-type"),
+        user: field("User"),
+        start_time: field("StartTime"),
+        minions: value
+            .get("Minions")
+            .and_then(Value::as_array)
+            .map(|minions| minions.len()),
+    })
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Options for compressing the results of the hosts.
+#[derive(Debug, Default)]
+struct CompressOptions {
+    /// Applied to the text of the results before they are compared.
+    rewrites: Vec<Rewrite>,
+    /// Replace the id and the short name of the host in the result and the
+    /// output with {{minion}} and its domain with {{domain}}.
+    substitute_minion: bool,
+}
+
+fn get_compressed(
+    results: MinionResults,
+    options: &CompressOptions,
+) -> DataMap<MinionResult, MinionResults> {
+    // compress output by removing the hostname and other fields that are
+    // different for every host and then just adding all results with that key
+    // to the map. The results keep their original values.
+    let mut compressed: DataMap<MinionResult, MinionResults> = DataMap::new();
+    for result in results {
+        compressed
+            .entry(result.compression_key(options))
+            .or_default()
+            .push(result);
+    }
+
+    compressed
+}
+
+/// Options for printing the compressed results.
+#[derive(Debug, Default)]
+struct PrintOptions {
+    /// List the values that were replaced by the rewrites for every host.
+    details: bool,
+    /// The rewrites that were applied to the results before compressing them.
+    rewrites: Vec<Rewrite>,
+    /// Cluster the results of the same command with at least the given
+    /// similarity of their lines.
+    cluster: Option<f64>,
+    hosts_display: HostsDisplay,
+    /// The attributes of the hosts from the inventory.
+    inventory: Inventory,
+    /// Print the hosts as the number of hosts with each value of the last
+    /// attribute for every value of the first attribute.
+    annotate: Vec<String>,
+    order: Order,
+}
+
+/// The order the compressed results are printed in. Results stay in their
+/// group and orchestration step.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Order {
+    /// Execution order for state runs and command order for everything else.
+    #[default]
+    Auto,
+    /// The order salt ran the states in by their __run_num__.
+    Execution,
+    /// Results with the most hosts first.
+    Hosts,
+    /// Failed results first.
+    Failures,
+    /// By the sls file of the state and then by execution order.
+    Sls,
+    /// By the command.
+    Command,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "auto" => Ok(Order::Auto),
+            "execution" => Ok(Order::Execution),
+            "hosts" => Ok(Order::Hosts),
+            "failures" => Ok(Order::Failures),
+            "sls" => Ok(Order::Sls),
+            "command" => Ok(Order::Command),
+            _ => Err(format!("unknown order {}", input)),
+        }
+    }
+}
+
+/// Sort the compressed results in the given order. The sort is stable so
+/// results that are equal in the order stay sorted by their command.
+fn sort_compressed(entries: &mut [(MinionResult, MinionResults)], order: Order) {
+    // the state of a result is the same for all hosts except for the run
+    // number so the lowest one is used
+    let run_num = |results: &MinionResults| {
+        results
+            .iter()
+            .filter_map(|result| result.state.as_ref().and_then(|state| state.run_num))
+            .min()
+            .unwrap_or(u64::MAX)
+    };
+
+    let order = match order {
+        Order::Auto if entries.iter().any(|(key, _)| key.state.is_some()) => Order::Execution,
+        Order::Auto => Order::Command,
+        order => order,
+    };
+
+    let position = |(key, _): &(MinionResult, MinionResults)| (key.group.clone(), key.step.clone());
+
+    match order {
+        Order::Auto | Order::Command => {}
+        Order::Execution => {
+            entries.sort_by_key(|entry| (position(entry), run_num(&entry.1)));
+        }
+        Order::Hosts => {
+            entries.sort_by_key(|entry| (position(entry), Reverse(entry.1.len())));
+        }
+        Order::Failures => {
+            entries.sort_by_key(|entry| (position(entry), entry.0.outcome() != Outcome::Failed));
+        }
+        Order::Sls => entries.sort_by_key(|entry| {
+            let sls = entry.0.state.as_ref().and_then(|state| state.sls.clone());
+            // results without a sls file come last
+            (position(entry), sls.is_none(), sls, run_num(&entry.1))
+        }),
+    }
+}
+
+/// Print the line with the hosts of a result. With attributes to annotate the
+/// hosts are counted by their attributes instead.
+fn print_hosts(hosts: &[String], options: &PrintOptions) {
+    if options.annotate.is_empty() {
+        println!(
+            "{}{}",
+            "HOSTS: ".cyan(),
+            format_hosts(hosts, options.hosts_display)
+        );
+        return;
+    }
+
+    for (index, line) in annotate_hosts(hosts, &options.inventory, &options.annotate)
+        .iter()
+        .enumerate()
+    {
+        let prefix = if index == 0 { "HOSTS: " } else { "       " };
+        println!("{}{}", prefix.cyan(), line);
+    }
+}
+
+/// Count the hosts by the values of their attributes in the inventory. With a
+/// single attribute every value is counted like "role=lb(3)". With two
+/// attributes there is a line for every value of the first attribute with the
+/// counts of the values of the last one like "role=lb: dca(3), hkg(2)".
+fn annotate_hosts(hosts: &[String], inventory: &Inventory, attributes: &[String]) -> Vec<String> {
+    let value = |host: &str, attribute: &str| {
+        inventory
+            .get(host)
+            .and_then(|attributes| attributes.get(attribute))
+            .map_or("unknown", String::as_str)
+            .to_string()
+    };
+
+    let first = &attributes[0];
+    let last = &attributes[attributes.len() - 1];
+
+    let mut counts: DataMap<String, DataMap<String, usize>> = DataMap::new();
+    for host in hosts {
+        *counts
+            .entry(value(host, first))
+            .or_default()
+            .entry(value(host, last))
+            .or_default() += 1;
+    }
+
+    let format_counts = |counts: &DataMap<String, usize>| {
+        counts
+            .iter()
+            .map(|(value, count)| format!("{}({})", value, count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    if attributes.len() == 1 {
+        let counts: DataMap<String, usize> = counts
+            .into_iter()
+            .map(|(value, counts)| (format!("{}={}", first, value), counts.values().sum()))
+            .collect();
+        return vec![format_counts(&counts)];
+    }
+
+    counts
+        .iter()
+        .map(|(value, counts)| format!("{}={}: {}", first, value, format_counts(counts)))
+        .collect()
+}
+
+/// How lists of hosts are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum HostsDisplay {
+    /// Every host separated by commas.
+    Full,
+    /// Hosts that only differ in a number collapsed into ranges like
+    /// web[01-40,43].dc1.example.com.
+    #[default]
+    Ranged,
+    /// Only the number of hosts.
+    Count,
+}
+
+impl FromStr for HostsDisplay {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "full" => Ok(HostsDisplay::Full),
+            "ranged" => Ok(HostsDisplay::Ranged),
+            "count" => Ok(HostsDisplay::Count),
+            _ => Err(format!("unknown hosts display {}", input)),
+        }
+    }
+}
+
+/// Format a list of hosts for printing. The hosts are sorted naturally so
+/// web2 comes before web10.
+fn format_hosts(hosts: &[String], display: HostsDisplay) -> String {
+    let mut hosts: Vec<&str> = hosts.iter().map(String::as_str).collect();
+    hosts.sort_by(|left, right| natural_cmp(left, right));
+    hosts.dedup();
+
+    match display {
+        HostsDisplay::Full => hosts.join(", "),
+        HostsDisplay::Count => format!(
+            "{} host{}",
+            hosts.len(),
+            if hosts.len() == 1 { "" } else { "s" }
+        ),
+        HostsDisplay::Ranged => {
+            // hosts that only differ in the last number of their short name
+            // grouped by the parts around the number with the numbers
+            let mut groups: DataMap<(&str, &str, &str), Vec<&str>> = DataMap::new();
+            let mut single = Vec::new();
+
+            for host in hosts {
+                let (short, domain) = match host.find('.') {
+                    Some(index) => host.split_at(index),
+                    None => (host, ""),
+                };
+
+                let end = match short.rfind(|c: char| c.is_ascii_digit()) {
+                    Some(end) => end + 1,
+                    None => {
+                        single.push(host.to_string());
+                        continue;
+                    }
+                };
+                let start = short[..end]
+                    .rfind(|c: char| !c.is_ascii_digit())
+                    .map_or(0, |start| start + 1);
+
+                groups
+                    .entry((&short[..start], &short[end..], domain))
+                    .or_default()
+                    .push(&short[start..end]);
+            }
+
+            // every entry is sorted by its first host
+            let mut formatted: Vec<(String, String)> = single
+                .into_iter()
+                .map(|host| (host.clone(), host))
+                .collect();
+
+            for ((prefix, suffix, domain), numbers) in groups {
+                let first = format!("{}{}{}{}", prefix, numbers[0], suffix, domain);
+
+                if numbers.len() == 1 {
+                    formatted.push((first.clone(), first));
+                    continue;
+                }
+
+                let ranged = format!(
+                    "{}[{}]{}{}",
+                    prefix,
+                    format_ranges(&numbers),
+                    suffix,
+                    domain
+                );
+                formatted.push((first, ranged));
+            }
+
+            formatted.sort_by(|(left, _), (right, _)| natural_cmp(left, right));
+            formatted
+                .into_iter()
+                .map(|(_, formatted)| formatted)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    }
+}
+
+/// Collapse sorted numbers into ranges like "01-40,43". Numbers with leading
+/// zeros are only part of a range with numbers of the same width.
+fn format_ranges(numbers: &[&str]) -> String {
+    let mut ranges: Vec<(&str, &str)> = Vec::new();
+
+    for number in numbers {
+        if let Some((_, last)) = ranges.last_mut() {
+            let follows = match (last.parse::<u64>(), number.parse::<u64>()) {
+                (Ok(left), Ok(right)) => left + 1 == right,
+                _ => false,
+            };
+            let padded = last.starts_with('0') || number.starts_with('0');
+
+            if follows && (!padded || last.len() == number.len()) {
+                *last = number;
+                continue;
+            }
+        }
+
+        ranges.push((number, number));
+    }
+
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Compare strings by comparing numbers in them by their value instead of
+/// character by character.
+fn natural_cmp(left: &str, right: &str) -> Ordering {
+    let mut left = left.chars().peekable();
+    let mut right = right.chars().peekable();
+
+    loop {
+        match (left.peek().copied(), right.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) if l.is_ascii_digit() && r.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut number = String::new();
+                    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+                        number.push(c);
+                        chars.next();
+                    }
+                    number
+                };
+
+                let l = take_number(&mut left);
+                let r = take_number(&mut right);
+
+                let ordering = l
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&r.trim_start_matches('0').len())
+                    .then_with(|| l.trim_start_matches('0').cmp(r.trim_start_matches('0')))
+                    .then_with(|| l.len().cmp(&r.len()));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(l), Some(r)) => {
+                if l != r {
+                    return l.cmp(&r);
+                }
+                left.next();
+                right.next();
+            }
+        }
+    }
+}
+
+/// A member of a cluster with the lines that differ from the representative of
+/// the cluster.
+#[derive(Debug, PartialEq)]
+struct Variant {
+    hosts: Vec<String>,
+    /// Lines of the representative the member does not have.
+    removed: Vec<String>,
+    /// Lines of the member the representative does not have.
+    added: Vec<String>,
+}
+
+/// Cluster results of the same command that only differ in some lines of their
+/// result and output. The result with the most hosts represents the cluster
+/// and gets the results of all members. The differences of the members are
+/// returned as variants of the representative.
+fn get_clusters(
+    compressed: DataMap<MinionResult, MinionResults>,
+    similarity: f64,
+) -> (
+    DataMap<MinionResult, MinionResults>,
+    DataMap<MinionResult, Vec<Variant>>,
+) {
+    // results with the same outcome that are the same except for the result
+    // and the output
+    let mut groups: DataMap<(MinionResult, Outcome), Vec<(MinionResult, MinionResults)>> =
+        DataMap::new();
+    for (key, results) in compressed {
+        let mut group = key.clone();
+        group.result = None;
+        group.output = None;
+        if let Some(ref mut state) = group.state {
+            state.comment = None;
+            state.changes = None;
+        }
+
+        groups
+            .entry((group, key.outcome()))
+            .or_default()
+            .push((key, results));
+    }
+
+    let mut clustered = DataMap::new();
+    let mut variants = DataMap::new();
+
+    for (_, mut members) in groups {
+        members.sort_by_key(|(_, results)| Reverse(results.len()));
+
+        let mut clusters: Vec<(MinionResult, MinionResults, Vec<Variant>)> = Vec::new();
+        for (key, results) in members {
+            let lines = get_cluster_lines(&key);
+
+            let cluster = clusters.iter_mut().find(|(representative, ..)| {
+                line_similarity(&get_cluster_lines(representative), &lines) >= similarity
+            });
+
+            match cluster {
+                Some((representative, cluster_results, cluster_variants)) => {
+                    let representative_lines = get_cluster_lines(representative);
+
+                    cluster_variants.push(Variant {
+                        hosts: results.iter().map(|result| result.host.clone()).collect(),
+                        removed: representative_lines
+                            .iter()
+                            .filter(|line| !lines.contains(line))
+                            .map(ToString::to_string)
+                            .collect(),
+                        added: lines
+                            .iter()
+                            .filter(|line| !representative_lines.contains(line))
+                            .map(ToString::to_string)
+                            .collect(),
+                    });
+                    cluster_results.extend(results);
+                }
+                None => clusters.push((key, results, Vec::new())),
+            }
+        }
+
+        for (representative, results, cluster_variants) in clusters {
+            if !cluster_variants.is_empty() {
+                variants.insert(representative.clone(), cluster_variants);
+            }
+            clustered.insert(representative, results);
+        }
+    }
+
+    (clustered, variants)
+}
+
+/// The lines of the result and the output that are compared for clustering.
+fn get_cluster_lines(result: &MinionResult) -> Vec<&str> {
+    result
+        .result_text()
+        .into_iter()
+        .chain(result.output_text())
+        .flat_map(str::lines)
+        .collect()
+}
+
+/// The share of lines that both have of all distinct lines.
+fn line_similarity(left: &[&str], right: &[&str]) -> f64 {
+    let left: DataSet<&str> = left.iter().copied().collect();
+    let right: DataSet<&str> = right.iter().copied().collect();
+
+    let union = left.union(&right).count();
+    if union == 0 {
+        return 1.0;
+    }
+
+    left.intersection(&right).count() as f64 / union as f64
+}
+
+fn print_compressed(
+    compressed: DataMap<MinionResult, MinionResults>,
+    filter: &Filter,
+    options: &PrintOptions,
+) {
+    let mut succeeded_hosts = DataSet::default();
+    let mut failed_hosts = DataSet::default();
+
+    let mut filter_command = DataSet::default();
+    let mut filter_failed = DataSet::default();
+    let mut filter_outcome = DataSet::default();
+    let mut filter_result = DataSet::default();
+    let mut filter_output = DataSet::default();
+    let mut filter_succeeded = DataSet::default();
+    let mut filter_unchanged = 0;
+
+    let unparsable = get_unparsable(&compressed);
+
+    let (compressed, variants) = match options.cluster {
+        Some(similarity) => get_clusters(compressed, similarity),
+        None => (compressed, DataMap::new()),
+    };
+
+    let mut compressed: Vec<(MinionResult, MinionResults)> = compressed.into_iter().collect();
+    sort_compressed(&mut compressed, options.order);
+
+    // the step of an orchestration that was printed last
+    let mut current_step = None;
+    // the group of hosts that was printed last
+    let mut current_group = None;
+
+    for (result, results) in compressed {
+        let result_variants = variants.get(&result);
+
+        let outcome = result.outcome();
+
+        let mut unchanged = false;
+        let mut kept = Vec::new();
+        for host_result in results {
+            let host = host_result.host.clone();
+            match filter.check(&result, &host_result) {
+                None => kept.push(host_result),
+                Some(Filtered::Command) => {
+                    filter_command.insert(host);
+                }
+                Some(Filtered::Failed) => {
+                    filter_failed.insert(host);
+                }
+                Some(Filtered::Outcome) => {
+                    filter_outcome.insert(host);
+                }
+                Some(Filtered::Output) => {
+                    filter_output.insert(host);
+                }
+                Some(Filtered::Result) => {
+                    filter_result.insert(host);
+                }
+                Some(Filtered::Succeeded) => {
+                    filter_succeeded.insert(host);
+                }
+                Some(Filtered::Unchanged) => unchanged = true,
+            }
+        }
+
+        // unchanged results are counted once for all of their hosts
+        if unchanged {
+            filter_unchanged += 1;
+        }
+
+        let results = kept;
+        if results.is_empty() {
+            continue;
+        }
+
+        // the values replaced by the rewrites with the hosts that had them
+        let mut rewritten: DataMap<Vec<String>, Vec<String>> = DataMap::new();
+        if options.details {
+            for result in &results {
+                let values = result.clone().rewrite(&options.rewrites);
+                if !values.is_empty() {
+                    rewritten
+                        .entry(values)
+                        .or_default()
+                        .push(result.host.clone());
+                }
+            }
+        }
+
+        let hosts: Vec<String> = results.into_iter().map(|result| result.host).collect();
+
+        if result.group.is_some() && result.group != current_group {
+            current_group = result.group.clone();
+            current_step = None;
+
+            println!();
+            println!("{}", "##########".blue().bold());
+            println!(
+                "{}",
+                format!("GROUP: {}", current_group.as_ref().unwrap())
+                    .blue()
+                    .bold()
+            );
+            println!("{}", "##########".blue().bold());
+        }
+
+        if result.step.is_some() && result.step != current_step {
+            current_step = result.step.clone();
+
+            println!();
+            println!("{}", "==========".magenta().bold());
+            println!(
+                "{}",
+                format!("STEP: {}", current_step.as_ref().unwrap().name)
+                    .magenta()
+                    .bold()
+            );
+            println!("{}", "==========".magenta().bold());
+        }
+
+        println!();
+        println!("{}", "----------".bold());
+        println!();
+
+        // state, command info
+        {
+            if result.command.is_some() {
+                println!("{}", "------".purple());
+
+                if result.command.is_some() {
+                    println!(
+                        "{}",
+                        format!("COMMAND: {}", result.command.clone().unwrap()).purple()
+                    );
+                }
+
+                if let Some(ref state) = result.state {
+                    let fields = [
+                        ("SLS", &state.sls),
+                        ("ID", &state.id),
+                        ("NAME", &state.name),
+                    ];
+                    for (name, field) in fields.iter() {
+                        if let Some(ref value) = field {
+                            println!("{}", format!("{}: {}", name, value).purple());
+                        }
+                    }
+                }
+
+                println!("{}\n", "------".purple());
+            }
+        }
+
+        // hosts
+        {
+            println!("{}", "------".cyan());
+            print_hosts(&hosts, options);
+
+            if !rewritten.is_empty() {
+                println!("{}", "VALUES:".cyan());
+                for (values, hosts) in rewritten {
+                    println!(
+                        "{}{}",
+                        format!("{}: ", format_hosts(&hosts, options.hosts_display)).cyan(),
+                        values.join("; ")
+                    );
+                }
+            }
+            println!("{}\n", "------".cyan());
+        }
+
+        // output
+        {
+            println!("{}", "------".yellow());
+
+            match result.retcode {
+                Retcode::Success => {
+                    for host in hosts {
+                        succeeded_hosts.insert(host);
+                    }
+                    println!("{}{}", "RETURN CODE: ".yellow(), "Success".green())
+                }
+                Retcode::Failure(..) => {
+                    for host in hosts {
+                        failed_hosts.insert(host);
+                    }
+                    println!(
+                        "{}{}",
+                        "RETURN CODE: ".yellow(),
+                        result.retcode.to_string().red()
+                    )
+                }
+            }
+
+            let outcome_string = outcome.to_string();
+            let outcome_string = match outcome {
+                Outcome::Succeeded => outcome_string.green(),
+                Outcome::Failed => outcome_string.red(),
+                Outcome::WouldChange => outcome_string.cyan(),
+                Outcome::Changed => outcome_string.blue(),
+            };
+            println!("{}{}", "OUTCOME: ".yellow(), outcome_string);
+
+            if let Some(result) = result.result_text() {
+                println!("{}", "RESULT:".yellow());
+                println!("{}\n", result);
+            }
+
+            if let Some(ref state) = result.state {
+                if !state.warnings.is_empty() {
+                    println!("{}", "WARNINGS:".yellow());
+                    for warning in &state.warnings {
+                        println!("{}", warning);
+                    }
+                    println!();
+                }
+            }
+
+            println!("{}", "OUTPUT:".yellow());
+            if let Some(output) = result.output_text() {
+                for line in output.lines() {
+                    if line.starts_with('-') {
+                        println!("{}", line.red());
+                        continue;
+                    }
+
+                    if line.starts_with('+') {
+                        println!("{}", line.green());
+                        continue;
+                    }
+
+                    println!("{}", line);
+                }
+            } else {
+                println!("No changes");
+            }
+
+            if let Some(variants) = result_variants {
+                println!();
+                println!("{}", "VARIANTS:".yellow());
+                for variant in variants {
+                    println!(
+                        "{}",
+                        format!("{}:", format_hosts(&variant.hosts, options.hosts_display)).cyan()
+                    );
+                    for line in &variant.removed {
+                        println!("{}", format!("- {}", line).red());
+                    }
+                    for line in &variant.added {
+                        println!("{}", format!("+ {}", line).green());
+                    }
+                }
+            }
+            println!("{}", "------".yellow());
+        }
+    }
+
+    println!();
+
+    print_filter_statistics("command", filter_command.len());
+    print_filter_statistics("result", filter_result.len());
+    print_filter_statistics("output", filter_output.len());
+    print_filter_statistics("failed", filter_failed.len());
+    print_filter_statistics("outcome", filter_outcome.len());
+    print_filter_statistics("succeeded", filter_succeeded.len());
+    print_filter_statistics("changed", filter_unchanged);
+
+    info!(
+        "succeeded host{}: {}",
+        if succeeded_hosts.len() > 1 || succeeded_hosts.is_empty() {
+            "s"
+        } else {
+            ""
+        },
+        succeeded_hosts.len()
+    );
+    info!(
+        "failed host{}: {}",
+        if failed_hosts.len() > 1 || failed_hosts.is_empty() {
+            "s"
+        } else {
+            ""
+        },
+        failed_hosts.len()
+    );
+
+    print_unparsable_statistics(unparsable);
+}
+
+/// Get the reasons why hosts could not be interpreted with the number of hosts.
+fn get_unparsable(compressed: &DataMap<MinionResult, MinionResults>) -> DataMap<String, usize> {
+    let mut unparsable: DataMap<String, usize> = DataMap::new();
+    for (result, results) in compressed {
+        if let Retcode::Failure(_, FailureCategory::Unparsable) = result.retcode {
+            let reason = result.output.clone().unwrap_or_default();
+            *unparsable.entry(reason).or_default() += results.len();
+        }
+    }
+
+    unparsable
+}
+
+/// Print a summary line for every path with the values at that path and the
+/// number of hosts that have them. The hosts are listed for all values except
+/// the most common one if there is one.
+fn print_generic(
+    compressed: DataMap<MinionResult, MinionResults>,
+    filter: &Filter,
+    options: &PrintOptions,
+) {
+    let unparsable = get_unparsable(&compressed);
+
+    let (compressed, removed) = get_filtered(compressed, filter);
+    let filtered: DataSet<String> = removed.into_iter().map(|result| result.host).collect();
+
+    let mut paths: DataMap<String, DataMap<String, Vec<String>>> = DataMap::new();
+    for (result, results) in compressed {
+        let path = result.command.unwrap_or_else(|| "return".to_string());
+        let value = result.result.or(result.output).unwrap_or_default();
+
+        paths
+            .entry(path)
+            .or_default()
+            .entry(value)
+            .or_default()
+            .extend(results.into_iter().map(|result| result.host));
+    }
+
+    for (path, values) in paths {
+        let mut values: Vec<(String, Vec<String>)> = values.into_iter().collect();
+        values.sort_by_key(|(_, hosts)| Reverse(hosts.len()));
+
+        let summary: Vec<String> = values
+            .iter()
+            .map(|(value, hosts)| {
+                format!(
+                    "{} on {} host{}",
+                    value.replace('\n', "\\n"),
+                    hosts.len(),
+                    if hosts.len() > 1 { "s" } else { "" }
+                )
+            })
+            .collect();
+
+        println!("{}={}", path.purple(), summary.join(", "));
+
+        // only skip the most common value if there is one
+        let skip = match values.get(1) {
+            Some((_, hosts)) if hosts.len() == values[0].1.len() => 0,
+            _ => 1,
+        };
+
+        for (value, hosts) in values.iter().skip(skip) {
+            println!(
+                "  {}{}",
+                format!("{}: ", value.replace('\n', "\\n")).cyan(),
+                format_hosts(hosts, options.hosts_display)
+            );
+        }
+    }
+
+    println!();
+
+    info!(
+        "filtered host{}: {}",
+        if filtered.len() > 1 || filtered.is_empty() {
+            "s"
+        } else {
+            ""
+        },
+        filtered.len()
+    );
+    print_unparsable_statistics(unparsable);
+}
+
+/// Remove the results of the hosts that do not pass the filter and the keys
+/// without any results left. Returns the remaining and the removed results.
+fn get_filtered(
+    compressed: DataMap<MinionResult, MinionResults>,
+    filter: &Filter,
+) -> (DataMap<MinionResult, MinionResults>, MinionResults) {
+    let mut remaining = DataMap::new();
+    let mut removed = Vec::new();
+
+    for (key, results) in compressed {
+        let (results, filtered): (MinionResults, MinionResults) = results
+            .into_iter()
+            .partition(|result| filter.is_match(&key, result));
+        removed.extend(filtered);
+
+        if !results.is_empty() {
+            remaining.insert(key, results);
+        }
+    }
+
+    (remaining, removed)
+}
+
+/// Group the hosts by the keys of all their results so hosts that had the same
+/// results for every command end up in the same group. The groups are sorted
+/// by their number of hosts.
+fn get_fingerprints(
+    compressed: &DataMap<MinionResult, MinionResults>,
+) -> Vec<(DataSet<&MinionResult>, Vec<String>)> {
+    let mut hosts: DataMap<&str, DataSet<&MinionResult>> = DataMap::new();
+    for (key, results) in compressed {
+        for result in results {
+            hosts.entry(result.host.as_str()).or_default().insert(key);
+        }
+    }
+
+    let mut fingerprints: DataMap<DataSet<&MinionResult>, Vec<String>> = DataMap::new();
+    for (host, keys) in hosts {
+        fingerprints.entry(keys).or_default().push(host.to_string());
+    }
+
+    let mut fingerprints: Vec<_> = fingerprints.into_iter().collect();
+    fingerprints.sort_by_key(|(_, hosts)| Reverse(hosts.len()));
+
+    fingerprints
+}
+
+/// Print the hosts grouped by the results of all their commands. Only the
+/// results that are different from the group with the most hosts are printed
+/// for the other groups. Results that do not pass the filter are not part of
+/// the fingerprints.
+fn print_fingerprints(
+    compressed: DataMap<MinionResult, MinionResults>,
+    filter: &Filter,
+    options: &PrintOptions,
+) {
+    let unparsable = get_unparsable(&compressed);
+    let (compressed, removed) = get_filtered(compressed, filter);
+    let filtered = removed.len();
+    let fingerprints = get_fingerprints(&compressed);
+
+    let majority = fingerprints
+        .first()
+        .map(|(keys, _)| keys.clone())
+        .unwrap_or_default();
+
+    for (index, (keys, hosts)) in fingerprints.iter().enumerate() {
+        println!();
+        println!("{}", "----------".bold());
+        println!(
+            "{}",
+            format!(
+                "FINGERPRINT {}: {} host{}{}",
+                index + 1,
+                hosts.len(),
+                if hosts.len() > 1 { "s" } else { "" },
+                if index == 0 { " (majority)" } else { "" }
+            )
+            .bold()
+        );
+        print_hosts(hosts, options);
+
+        if index == 0 {
+            println!("{}", "RESULTS:".yellow());
+            for key in keys {
+                println!(
+                    "  {} ({})",
+                    key.command.as_deref().unwrap_or("no command"),
+                    key.outcome()
+                );
+            }
+            continue;
+        }
+
+        let only: Vec<_> = keys.difference(&majority).collect();
+        if !only.is_empty() {
+            println!("{}", "ONLY IN THESE HOSTS:".yellow());
+            for key in only {
+                print_fingerprint_result('+', key);
+            }
+        }
+
+        let missing: Vec<_> = majority.difference(keys).collect();
+        if !missing.is_empty() {
+            println!("{}", "NOT IN THESE HOSTS:".yellow());
+            for key in missing {
+                print_fingerprint_result('-', key);
+            }
+        }
+    }
+
+    println!();
+
+    info!(
+        "fingerprint{}: {}",
+        if fingerprints.len() == 1 { "" } else { "s" },
+        fingerprints.len()
+    );
+    info!(
+        "filtered result{}: {}",
+        if filtered == 1 { "" } else { "s" },
+        filtered
+    );
+
+    print_unparsable_statistics(unparsable);
+}
+
+/// Print the command and outcome of a result that is different from the
+/// majority with its result and output indented below it.
+fn print_fingerprint_result(sign: char, result: &MinionResult) {
+    let line = format!(
+        "{} {} ({})",
+        sign,
+        result.command.as_deref().unwrap_or("no command"),
+        result.outcome()
+    );
+
+    if sign == '+' {
+        println!("{}", line.green());
+    } else {
+        println!("{}", line.red());
+    }
+
+    for text in result.result_text().into_iter().chain(result.output_text()) {
+        for line in text.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+fn print_metadata(metadata: &Metadata) {
+    if let Some(ref job) = metadata.job {
+        println!("{}", "------".blue());
+
+        let fields = [
+            ("JID: ", &job.jid),
+            ("FUNCTION: ", &job.function),
+            ("ARGUMENTS: ", &job.arguments),
+            ("TARGET: ", &job.target),
+            ("TARGET TYPE: ", &job.target_type),
+            ("USER: ", &job.user),
+            ("START TIME: ", &job.start_time),
+        ];
+
+        for (name, field) in fields.iter() {
+            match field {
+                Some(ref value) if !value.is_empty() => println!("{}{}", name.blue(), value),
+                _ => {}
+            }
+        }
+
+        if let Some(minions) = job.minions {
+            println!("{}{}", "TARGETED MINIONS: ".blue(), minions);
+        }
+
+        println!("{}", "------".blue());
+    }
+
+    if metadata.messages.is_empty() {
+        return;
+    }
+
+    println!("{}", "------".blue());
+    for message in &metadata.messages {
+        println!("{}{}", "MESSAGE: ".blue(), message);
+    }
+    println!("{}", "------".blue());
+}
+
+fn print_unparsable_statistics(unparsable: DataMap<String, usize>) {
+    let unparsable_hosts: usize = unparsable.values().sum();
+    info!(
+        "host{} that could not be interpreted: {}",
+        if unparsable_hosts > 1 || unparsable_hosts == 0 {
+            "s"
+        } else {
+            ""
+        },
+        unparsable_hosts
+    );
+    for (reason, count) in unparsable {
+        info!("  {}: {}", reason, count);
+    }
+}
+
+fn print_filter_statistics(stats: &str, count: usize) {
+    info!(
+        "filtered {} state{}: {}",
+        stats,
+        if count > 1 || count == 0 { "s" } else { "" },
+        count
+    );
+}
+
+fn write_save_file(host_data: &str) {
+    let save_filename = format!(
+        "/tmp/salt-compressor_{}.json",
+        chrono::Utc::now().timestamp()
+    );
+    let mut save_file = File::create(save_filename.clone()).expect("can not create save_file");
     save_file
         .write_all(host_data.as_bytes())
         .expect("can not write host data to save_file");
@@ -574,59 +2799,1108 @@ fn write_save_file(host_data: &str) {
     );
 }
 
-fn cleanup_input_data<'a>(
+/// Remove the lines salt prints for minions that did not return anything from
+/// the input data and get the hosts with their failure category and message.
+/// The host of a line that does not contain the id of the minion is taken from
+/// the line before it like in the output of the text outputters. The removed
+/// lines are left empty so line numbers in errors stay the same.
+fn cleanup_input_data(
+    input_data: &str,
+    patterns: &[FailurePattern],
+) -> (String, DataMap<String, (FailureCategory, String)>) {
+    let host_line = Regex::new(r"^(\S+):\s*$").expect("regex for catching host lines is not valid");
+
+    let mut failed_minions = DataMap::default();
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in input_data.lines() {
+        let pattern = match patterns.iter().find(|pattern| pattern.regex.is_match(line)) {
+            Some(pattern) => pattern,
+            None => {
+                lines.push(line);
+                continue;
+            }
+        };
+
+        let captures = pattern.regex.captures(line).unwrap();
+        let host = match captures.name("host") {
+            Some(host) => host.as_str().to_string(),
+            None => match lines
+                .last()
+                .copied()
+                .and_then(|last| host_line.captures(last))
+            {
+                Some(host) => {
+                    let host = host[1].to_string();
+                    *lines.last_mut().unwrap() = "";
+                    host
+                }
+                None => {
+                    warn!("can not find the host for line \"{}\"", line.trim());
+                    "*".to_string()
+                }
+            },
+        };
+
+        failed_minions.insert(host, (pattern.category.clone(), pattern.message(line)));
+        lines.push("");
+    }
+
+    let mut input_data = lines.join("\n");
+    input_data.push('\n');
+
+    (input_data, failed_minions)
+}
+
+/// A pattern for a message salt prints for minions that did not return
+/// anything. The host is taken from the named group "host" if the pattern has
+/// one.
+#[derive(Debug, Clone)]
+struct FailurePattern {
+    regex: Regex,
+    category: FailureCategory,
+    /// Used instead of the matched line as the output of the host.
+    message: Option<String>,
+}
+
+impl FailurePattern {
+    fn new(regex: &str, category: FailureCategory, message: Option<&str>) -> Self {
+        Self {
+            regex: Regex::new(regex).expect("regex of builtin failure pattern is not valid"),
+            category,
+            message: message.map(ToString::to_string),
+        }
+    }
+
+    /// The messages known from the different versions of salt.
+    fn builtin() -> Vec<FailurePattern> {
+        vec![
+            FailurePattern::new(
+                r"^Minion (?P<host>\S+) did not respond\. No job will be sent\.$",
+                FailureCategory::NotResponded,
+                Some("Minion did not respond. No job will be sent."),
+            ),
+            FailurePattern::new(
+                r"^minion (?P<host>\S+) was already deleted from tracker, probably a duplicate key",
+                FailureCategory::DuplicateKey,
+                Some("Minion was already deleted from tracker, probably a duplicate key."),
+            ),
+            FailurePattern::new(
+                r"^\s*Minion did not return\. \[Not connected\]\s*$",
+                FailureCategory::NotConnected,
+                None,
+            ),
+            FailurePattern::new(
+                r"^\s*Minion did not return\. \[No response\]\s*$",
+                FailureCategory::NotResponded,
+                None,
+            ),
+            FailurePattern::new(
+                r"^\s*ERROR: No return received",
+                FailureCategory::NotResponded,
+                Some("ERROR: No return received."),
+            ),
+        ]
+    }
+
+    /// Get the category and message of the first pattern that matches the line.
+    fn find(patterns: &[FailurePattern], line: &str) -> Option<(FailureCategory, String)> {
+        patterns
+            .iter()
+            .find(|pattern| pattern.regex.is_match(line))
+            .map(|pattern| (pattern.category.clone(), pattern.message(line)))
+    }
+
+    fn message(&self, line: &str) -> String {
+        match self.message {
+            Some(ref message) => message.clone(),
+            None => line.trim().to_string(),
+        }
+    }
+}
+
+/// Errors of the yaml files that configure salt-compressor. The files contain a
+/// list of entries and errors of an entry contain its index.
+#[derive(Debug)]
+enum ConfigError {
+    ReadFile(PathBuf, io::Error),
+    InvalidYaml(serde_yaml::Error),
+    NotAList,
+    MissingField(usize, &'static str),
+    InvalidRegex(usize, regex::Error),
+    InvalidValue(usize, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::ReadFile(ref path, ref e) => {
+                write!(f, "can not read {}: {}", path.display(), e)
+            }
+            ConfigError::InvalidYaml(ref e) => write!(f, "invalid yaml: {}", e),
+            ConfigError::NotAList => write!(f, "entries are not a list"),
+            ConfigError::MissingField(ref index, ref field) => {
+                write!(f, "entry {} does not have the field {}", index, field)
+            }
+            ConfigError::InvalidRegex(ref index, ref e) => {
+                write!(f, "entry {} does not have a valid regex: {}", index, e)
+            }
+            ConfigError::InvalidValue(ref index, ref e) => write!(f, "entry {}: {}", index, e),
+        }
+    }
+}
+
+/// Read the list of entries from a yaml file.
+fn read_config_entries(path: &Path) -> Result<Vec<Value>, ConfigError> {
+    let data =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::ReadFile(path.to_owned(), e))?;
+    let value = yaml_to_json(serde_yaml::from_str(&data).map_err(ConfigError::InvalidYaml)?);
+
+    match value {
+        Value::Array(entries) => Ok(entries),
+        _ => Err(ConfigError::NotAList),
+    }
+}
+
+/// Read additional failure patterns from a yaml file with a list of entries
+/// with the fields "pattern", "category" and optionally "message".
+fn read_failure_patterns(path: &Path) -> Result<Vec<FailurePattern>, ConfigError> {
+    let mut patterns = Vec::new();
+
+    for (index, entry) in read_config_entries(path)?.iter().enumerate() {
+        let field = |name: &'static str| {
+            entry
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or(ConfigError::MissingField(index, name))
+        };
+
+        patterns.push(FailurePattern {
+            regex: Regex::new(field("pattern")?)
+                .map_err(|e| ConfigError::InvalidRegex(index, e))?,
+            category: field("category")?
+                .parse()
+                .map_err(|e| ConfigError::InvalidValue(index, e))?,
+            message: entry
+                .get("message")
+                .and_then(Value::as_str)
+                .map(ToString::to_string),
+        });
+    }
+
+    Ok(patterns)
+}
+
+/// Read rewrite rules from a yaml file with a list of entries with the fields
+/// "pattern", "replacement" and optionally "fields". Without fields the rule
+/// applies to the command, the result and the output.
+fn read_rewrites(path: &Path) -> Result<Vec<Rewrite>, ConfigError> {
+    let mut rewrites = Vec::new();
+
+    for (index, entry) in read_config_entries(path)?.iter().enumerate() {
+        let field = |name: &'static str| {
+            entry
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or(ConfigError::MissingField(index, name))
+        };
+
+        let fields = match entry.get("fields") {
+            None => vec![
+                RewriteField::Command,
+                RewriteField::Result,
+                RewriteField::Output,
+            ],
+            Some(Value::Array(fields)) => fields
+                .iter()
+                .map(|field| {
+                    field
+                        .as_str()
+                        .unwrap_or_default()
+                        .parse()
+                        .map_err(|e| ConfigError::InvalidValue(index, e))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(ConfigError::InvalidValue(
+                    index,
+                    "fields is not a list".to_string(),
+                ))
+            }
+        };
+
+        rewrites.push(Rewrite {
+            regex: Regex::new(field("pattern")?)
+                .map_err(|e| ConfigError::InvalidRegex(index, e))?,
+            replacement: field("replacement")?.to_string(),
+            fields,
+        });
+    }
+
+    Ok(rewrites)
+}
+
+/// Read the ids of the minions that are expected in the output. The file can
+/// be the output of salt-key --out json, a salt roster, a yaml list or contain
+/// one id per line.
+fn read_expected_minions(path: &Path) -> Result<DataSet<String>, ConfigError> {
+    let data =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::ReadFile(path.to_owned(), e))?;
+
+    let lines = data
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    // yaml can not parse a file without any content
+    if lines.clone().next().is_none() {
+        return Ok(DataSet::new());
+    }
+
+    let value = yaml_to_json(serde_yaml::from_str(&data).map_err(ConfigError::InvalidYaml)?);
+
+    let minions = match value {
+        // salt-key lists the accepted minions under "minions"
+        Value::Object(ref object) => match object.get("minions") {
+            Some(Value::Array(minions)) => minions.iter().map(value_to_string).collect(),
+            _ => object.keys().cloned().collect(),
+        },
+        Value::Array(array) => array.iter().map(value_to_string).collect(),
+        // plain text with one id per line is parsed as a single scalar
+        _ => lines.map(ToString::to_string).collect(),
+    };
+
+    Ok(minions)
+}
+
+/// The attributes of every host like role or datacenter by the id of the host.
+type Inventory = DataMap<String, DataMap<String, String>>;
+
+/// Read the inventory from a csv file with a header or a yaml file. The yaml
+/// file can either map the ids of the hosts to their attributes like a salt
+/// roster or contain a list of hosts with an "id" field. Nested attributes like
+/// the grains in a roster are added with their own keys.
+fn read_inventory(path: &Path) -> Result<Inventory, ConfigError> {
+    let data =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::ReadFile(path.to_owned(), e))?;
+
+    if path.extension().and_then(|extension| extension.to_str()) == Some("csv") {
+        return read_csv_inventory(&data);
+    }
+
+    let value = yaml_to_json(serde_yaml::from_str(&data).map_err(ConfigError::InvalidYaml)?);
+
+    let attributes = |value: &Value| {
+        let mut attributes = DataMap::new();
+
+        if let Some(object) = value.as_object() {
+            for (key, value) in object {
+                match value {
+                    Value::Object(nested) => {
+                        for (key, value) in nested {
+                            if let Some(value) = render_scalar(value) {
+                                attributes.insert(key.clone(), value);
+                            }
+                        }
+                    }
+                    value => {
+                        if let Some(value) = render_scalar(value) {
+                            attributes.insert(key.clone(), value);
+                        }
+                    }
+                }
+            }
+        }
+
+        attributes
+    };
+
+    let mut inventory = Inventory::new();
+    match value {
+        Value::Object(hosts) => {
+            for (host, value) in hosts {
+                inventory.insert(host, attributes(&value));
+            }
+        }
+        Value::Array(entries) => {
+            for (index, entry) in entries.iter().enumerate() {
+                let host = entry
+                    .get("id")
+                    .map(value_to_string)
+                    .ok_or(ConfigError::MissingField(index, "id"))?;
+
+                let mut attributes = attributes(entry);
+                attributes.remove("id");
+                inventory.insert(host, attributes);
+            }
+        }
+        _ => return Err(ConfigError::NotAList),
+    }
+
+    Ok(inventory)
+}
+
+/// Read the inventory from csv data. The column "id" or "minion" contains the
+/// ids of the hosts, without one the first column is used.
+fn read_csv_inventory(data: &str) -> Result<Inventory, ConfigError> {
+    let mut lines = data
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header: Vec<String> = match lines.next() {
+        Some(header) => split_csv_line(header),
+        None => return Ok(Inventory::new()),
+    };
+
+    let id = header
+        .iter()
+        .position(|column| *column == "id" || *column == "minion")
+        .unwrap_or(0);
+
+    let mut inventory = Inventory::new();
+    for (index, line) in lines.enumerate() {
+        let columns = split_csv_line(line);
+
+        let host = columns
+            .get(id)
+            .filter(|host| !host.is_empty())
+            .ok_or(ConfigError::MissingField(index, "id"))?;
+
+        let attributes = header
+            .iter()
+            .zip(columns.iter())
+            .enumerate()
+            .filter(|(column, _)| *column != id)
+            .map(|(_, (name, value))| (name.to_string(), value.to_string()))
+            .collect();
+
+        inventory.insert(host.to_string(), attributes);
+    }
+
+    Ok(inventory)
+}
+
+/// Split a line of csv data into its fields. A field in double quotes can
+/// contain commas and a double quote is escaped by doubling it.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+#[derive(Debug)]
+enum InputError {
+    DocumentNotAnObject(usize),
+    InvalidJson(usize, serde_json::Error),
+    JidNotInInput(String),
+    InvalidYaml(usize, serde_yaml::Error),
+    InvalidMsgpack(PathBuf, rmpv::decode::Error),
+    JobNotFound(PathBuf),
+    NoDocument(&'static str),
+    ReadFile(PathBuf, io::Error),
+    UnexpectedLine(usize, String),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputError::DocumentNotAnObject(ref line) => write!(
+                f,
+                "json document starting at line {} is not an object and can not be merged",
+                line
+            ),
+            InputError::InvalidJson(ref line, ref e) => {
+                write!(f, "invalid json document starting at line {}: {}", line, e)
+            }
+            InputError::JidNotInInput(ref jid) => {
+                write!(f, "input does not contain returns of job {}", jid)
+            }
+            InputError::InvalidYaml(ref line, ref e) => {
+                write!(f, "invalid yaml document starting at line {}: {}", line, e)
+            }
+            InputError::InvalidMsgpack(ref path, ref e) => write!(
+                f,
+                "can not decode {} as json or msgpack: {}",
+                path.display(),
+                e
+            ),
+            InputError::JobNotFound(ref path) => {
+                write!(f, "can not find job in {}", path.display())
+            }
+            InputError::NoDocument(ref format) => {
+                write!(f, "input does not contain any {} document", format)
+            }
+            InputError::ReadFile(ref path, ref e) => {
+                write!(f, "can not read {}: {}", path.display(), e)
+            }
+            InputError::UnexpectedLine(ref line, ref content) => {
+                write!(f, "unexpected line {} in input: {}", line, content)
+            }
+        }
+    }
+}
+
+/// Parse the input data in the given format. With InputFormat::Auto the format
+/// is detected from the first line that is not a known salt message. The jid
+/// selects the job for inputs that can contain the returns of multiple jobs.
+fn parse_input(
     input_data: &str,
-) -> (
-    String,
-    std::collections::BTreeMap<std::string::String, &'a str>,
-) {
-    let mut failed_minions = DataMap::default();
+    input_format: InputFormat,
+    jid: Option<&str>,
+) -> Result<(Value, Metadata), InputError> {
+    let input_format = match input_format {
+        InputFormat::Auto => detect_input_format(input_data),
+        _ => input_format,
+    };
+
+    debug!("input format: {:?}", input_format);
+
+    let (value, metadata) = match input_format {
+        InputFormat::Event => return parse_event_input_data(input_data, jid),
+        InputFormat::Rawfile => return parse_rawfile_input_data(input_data, jid),
+        InputFormat::Yaml => parse_yaml_input_data(input_data)?,
+        _ => parse_input_data(input_data)?,
+    };
+
+    if let Some(jid) = jid {
+        check_jid(&value, jid)?;
+    }
+
+    Ok((value, metadata))
+}
+
+/// Check that the output contains the returns of the job with the jid. Only the
+/// output of jobs.list_job contains the jid of the job, all other outputs only
+/// contain the returns of one job which can not be selected.
+fn check_jid(value: &Value, jid: &str) -> Result<(), InputError> {
+    if !is_list_job(value) {
+        warn!(
+            "input only contains the returns of one job without its jid, ignoring jid {}",
+            jid
+        );
+        return Ok(());
+    }
+
+    match value.get("jid").map(value_to_string) {
+        Some(ref job) if job == jid => Ok(()),
+        Some(_) => Err(InputError::JidNotInInput(jid.to_string())),
+        None => {
+            warn!("job in the input does not have a jid, ignoring jid {}", jid);
+            Ok(())
+        }
+    }
+}
+
+fn detect_input_format(input_data: &str) -> InputFormat {
+    let noise_lines = get_noise_lines();
+
+    let first_line = input_data
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !noise_lines.iter().any(|regex| regex.is_match(line)));
+
+    match first_line {
+        Some(line) if is_event_line(line) => InputFormat::Event,
+        Some(line) if is_rawfile_line(line) => InputFormat::Rawfile,
+        Some(line) if !line.starts_with('{') && !line.starts_with('[') => InputFormat::Yaml,
+        _ => InputFormat::Json,
+    }
+}
+
+/// Event lines have the format "tag<TAB>json".
+fn is_event_line(line: &str) -> bool {
+    match line.find('\t') {
+        Some(index) => {
+            let tag = &line[..index];
+            !tag.is_empty()
+                && !tag.contains(char::is_whitespace)
+                && line[index + 1..].trim_start().starts_with('{')
+        }
+        None => false,
+    }
+}
+
+/// Lines of the rawfile_json returner are json objects that contain the
+/// return data of a job.
+fn is_rawfile_line(line: &str) -> bool {
+    match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(data)) => {
+            (data.contains_key("jid") && data.contains_key("id")) || data.contains_key("tag")
+        }
+        _ => false,
+    }
+}
+
+/// Lines salt prints in between the documents of the outputters.
+fn get_noise_lines() -> [Regex; 3] {
+    [
+        Regex::new(r"^Executing run on \[.*\]$")
+            .expect("regex for catching batch run lines is not valid"),
+        Regex::new(r"^ERROR: Minions returned with non-zero exit code$")
+            .expect("regex for catching non-zero exit code lines is not valid"),
+        Regex::new(r"^\[(TRACE|DEBUG|INFO|WARNING|ERROR|CRITICAL)\s*\]")
+            .expect("regex for catching salt log lines is not valid"),
+    ]
+}
+
+/// Extract all json documents from the input data and merge them into one
+/// object. Salt prints one document per minion when it is not run with
+/// --static or when it is run in batch mode. Known lines salt prints in between
+/// the documents are returned as metadata.
+fn parse_input_data(input_data: &str) -> Result<(Value, Metadata), InputError> {
+    let noise_lines = get_noise_lines();
+
+    let mut metadata = Metadata::default();
+    let mut documents = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let rest = input_data[position..].trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        position = input_data.len() - rest.len();
+        let line_number = input_data[..position].lines().count() + 1;
+
+        let line = rest.lines().next().unwrap_or_default().trim_end();
+        if noise_lines.iter().any(|regex| regex.is_match(line)) {
+            trace!("noise line {}: {}", line_number, line);
+            metadata.messages.push(line.to_string());
+            position += line.len();
+            continue;
+        }
+
+        if !rest.starts_with('{') && !rest.starts_with('[') {
+            return Err(InputError::UnexpectedLine(line_number, line.to_string()));
+        }
+
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        match stream.next() {
+            Some(Ok(document)) => documents.push((line_number, document)),
+            Some(Err(e)) => return Err(InputError::InvalidJson(line_number, e)),
+            None => break,
+        }
+        position += stream.byte_offset();
+    }
+
+    // salt prints nothing to stdout when it fails before any minion returned
+    if documents.is_empty() {
+        return Err(InputError::NoDocument("json"));
+    }
+
+    Ok((merge_documents(documents)?, metadata))
+}
+
+/// Extract all yaml documents from the input data and merge them into one
+/// object like parse_input_data does for json.
+fn parse_yaml_input_data(input_data: &str) -> Result<(Value, Metadata), InputError> {
+    let noise_lines = get_noise_lines();
+
+    let mut metadata = Metadata::default();
+    let mut documents = Vec::new();
+
+    // documents are separated by lines starting with "---" and we also split
+    // on the noise lines as salt prints them in between the documents
+    let mut document = String::new();
+    let mut document_start = 1;
+    for (index, line) in input_data.lines().enumerate() {
+        let line_number = index + 1;
+
+        let is_noise = noise_lines.iter().any(|regex| regex.is_match(line.trim()));
+        if is_noise || line.starts_with("---") || line.starts_with("...") {
+            if is_noise {
+                trace!("noise line {}: {}", line_number, line);
+                metadata.messages.push(line.trim().to_string());
+            }
+
+            if let Some(value) = parse_yaml_document(document_start, document.as_str())? {
+                documents.push((document_start, value));
+            }
+            document.clear();
+            document_start = line_number + 1;
+            continue;
+        }
+
+        document.push_str(line);
+        document.push('\n');
+    }
+
+    if let Some(value) = parse_yaml_document(document_start, document.as_str())? {
+        documents.push((document_start, value));
+    }
+
+    if documents.is_empty() {
+        return Err(InputError::NoDocument("yaml"));
+    }
+
+    Ok((merge_documents(documents)?, metadata))
+}
+
+fn parse_yaml_document(line_number: usize, document: &str) -> Result<Option<Value>, InputError> {
+    if document.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(document).map_err(|e| InputError::InvalidYaml(line_number, e))?;
+
+    Ok(Some(yaml_to_json(value)))
+}
+
+/// Convert a yaml value to the json value get_results works with. Keys of
+/// mappings that are not strings will be converted to strings.
+fn yaml_to_json(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else {
+                n.as_f64().map(Value::from).unwrap_or(Value::Null)
+            }
+        }
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(sequence) => {
+            Value::Array(sequence.into_iter().map(yaml_to_json).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match yaml_to_json(key) {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, yaml_to_json(value))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Extract the return events of jobs from an event stream like the one printed
+/// by "salt-run state.event pretty=False". Every line has the format
+/// "tag<TAB>json" and only events with tags like "salt/job/<jid>/ret/<minion>"
+/// are used.
+fn parse_event_input_data(
+    input_data: &str,
+    jid: Option<&str>,
+) -> Result<(Value, Metadata), InputError> {
+    let return_tag = get_return_tag();
+
+    let mut returns = Vec::new();
+    let mut skipped = 0;
+
+    for (index, line) in input_data.lines().enumerate() {
+        let line_number = index + 1;
 
-    // Cleanup input data from minions that either didnt return or had a duplicate
-    // key
-    let input_data = {
-        // match all hosts that have not returned as they are not in the json data
-        // format is normally like "Minion minionid did not respond. No job will be
-        // sent."
-        let catch_not_returned_minions =
-            Regex::new(r"(?m)^Minion (\S*) did not respond\. No job will be sent\.$")
-                .expect("regex for catching not returned minions is not valid");
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (tag, data) = match line.find('\t') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => return Err(InputError::UnexpectedLine(line_number, line.to_string())),
+        };
+
+        if !return_tag.is_match(tag) {
+            trace!("skipping event {}", tag);
+            skipped += 1;
+            continue;
+        }
+
+        let data: Value =
+            serde_json::from_str(data).map_err(|e| InputError::InvalidJson(line_number, e))?;
+        returns.push(data);
+    }
+
+    debug!("skipped {} events that are not job returns", skipped);
+
+    let (value, job) = returns_to_value(select_job(returns, jid)?);
+
+    let metadata = Metadata {
+        job,
+        ..Metadata::default()
+    };
+
+    Ok((value, metadata))
+}
+
+/// Extract the return data of jobs from a file written by the rawfile_json
+/// returner. Every line is a json object with the return data of one minion.
+/// Lines written by event_return contain the event tag and the return data.
+fn parse_rawfile_input_data(
+    input_data: &str,
+    jid: Option<&str>,
+) -> Result<(Value, Metadata), InputError> {
+    let return_tag = get_return_tag();
+
+    let mut returns = Vec::new();
+    let mut skipped = 0;
+
+    for (index, line) in input_data.lines().enumerate() {
+        let line_number = index + 1;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut data: Value =
+            serde_json::from_str(line).map_err(|e| InputError::InvalidJson(line_number, e))?;
+
+        if let Some(tag) = data.get("tag").and_then(Value::as_str) {
+            if !return_tag.is_match(tag) {
+                trace!("skipping event {}", tag);
+                skipped += 1;
+                continue;
+            }
+
+            data = data.get_mut("data").map(Value::take).unwrap_or_default();
+        }
+
+        returns.push(data);
+    }
+
+    debug!("skipped {} events that are not job returns", skipped);
+
+    let (value, job) = returns_to_value(select_job(returns, jid)?);
+
+    let metadata = Metadata {
+        job,
+        ..Metadata::default()
+    };
+
+    Ok((value, metadata))
+}
+
+fn get_return_tag() -> Regex {
+    Regex::new(r"^salt/job/[^/]+/ret/[^/]+$")
+        .expect("regex for catching return event tags is not valid")
+}
+
+/// Only keep the returns of the job with the given jid or of the latest job if
+/// no jid is given.
+fn select_job(returns: Vec<Value>, jid: Option<&str>) -> Result<Vec<Value>, InputError> {
+    let mut jobs: DataMap<String, Vec<Value>> = DataMap::new();
+    for data in returns {
+        let job = data.get("jid").map(value_to_string).unwrap_or_default();
+        jobs.entry(job).or_default().push(data);
+    }
+
+    let selected = match jid {
+        Some(jid) => jid.to_string(),
+        // jids are timestamps so the latest job has the highest one
+        None => match jobs.keys().max_by_key(|jid| (jid.len(), jid.as_str())) {
+            Some(jid) => jid.clone(),
+            None => return Ok(Vec::new()),
+        },
+    };
+
+    if jobs.len() > 1 {
+        info!(
+            "input contains the returns of {} jobs, using job {}",
+            jobs.len(),
+            selected
+        );
+    }
+
+    jobs.remove(&selected)
+        .ok_or(InputError::JidNotInInput(selected))
+}
+
+/// Convert the return data of jobs as sent by the minions to the format salt
+/// prints with --static. The job information is only set for the fields that
+/// are the same for all returns.
+fn returns_to_value(returns: Vec<Value>) -> (Value, Option<Job>) {
+    let mut minions = serde_json::Map::new();
+    let mut jids = DataSet::new();
+    let mut functions = DataSet::new();
+
+    for mut data in returns {
+        let minion = match data.get("id").and_then(Value::as_str) {
+            Some(id) => id.to_string(),
+            None => {
+                warn!("skipping return without minion id: {}", data);
+                continue;
+            }
+        };
+
+        if let Some(jid) = data.get("jid") {
+            jids.insert(value_to_string(jid));
+        }
 
-        let errmessage = "Minion did not respond. No job will be sent.";
-        for host in catch_not_returned_minions.captures_iter(input_data) {
-            failed_minions.insert(host[1].to_string(), errmessage);
+        let mut values = serde_json::Map::new();
+        for (from, to) in &[
+            ("return", "ret"),
+            ("retcode", "retcode"),
+            ("success", "success"),
+            ("fun", "fun"),
+        ] {
+            if let Some(value) = data.get_mut(*from) {
+                values.insert(to.to_string(), value.take());
+            }
         }
 
-        let data = catch_not_returned_minions
-            .replace_all(input_data, "")
-            .into_owned();
+        if let Some(function) = values.get("fun") {
+            functions.insert(value_to_string(function));
+        }
 
-        // match all hosts that have a duplicate key in the system
-        // like "minion minionid was already deleted from tracker, probably a duplicate
-        // key"
-        let catch_duplicate_key_minions = Regex::new(
-            r"(?m)^minion (\S*) was already deleted from tracker, probably a duplicate key",
-        )
-        .expect("regex for catching duplicate key minions is not valid");
+        if minions.contains_key(&minion) {
+            warn!(
+                "minion {} returned more than once, using the last result",
+                minion
+            );
+        }
+        minions.insert(minion, Value::Object(values));
+    }
 
-        let errmessage = "Minion was already deleted from tracker, probably a duplicate key.";
-        for host in catch_duplicate_key_minions.captures_iter(input_data) {
-            failed_minions.insert(host[1].to_string(), errmessage);
+    let single = |set: DataSet<String>| {
+        if set.len() == 1 {
+            set.into_iter().next()
+        } else {
+            None
         }
+    };
 
-        catch_duplicate_key_minions
-            .replace_all(data.as_str(), "")
-            .into_owned()
+    let jid = single(jids);
+    let job = Job {
+        start_time: jid.as_ref().and_then(|jid| jid_to_time(jid)),
+        jid,
+        function: single(functions),
+        ..Job::default()
     };
 
-    let no_return_received = "ERROR: No return received";
-    let input_data = if input_data.contains(no_return_received) {
-        failed_minions.insert('*'.to_string(), "ERROR: No return received.");
-        input_data.replace(no_return_received, "")
+    let job = if job == Job::default() {
+        None
     } else {
-        input_data
+        Some(job)
     };
 
-    // clean up hosts that have not returned from the json data
-    (input_data, failed_minions)
+    (Value::Object(minions), job)
+}
+
+/// Read the returns of a job from the local job cache of the master. The cache
+/// has a directory for every job under <cache_dir>/<hash>/<hash> that contains
+/// a file with the jid, the load of the job in .load.p and a directory for
+/// every minion with its return in return.p.
+fn read_job_cache(cache_dir: &Path, jid: &str) -> Result<(Value, Metadata), InputError> {
+    let job_dir = find_job_dir(cache_dir, jid)?;
+    debug!("job dir: {}", job_dir.display());
+
+    let mut minions = serde_json::Map::new();
+
+    let entries =
+        std::fs::read_dir(&job_dir).map_err(|e| InputError::ReadFile(job_dir.clone(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| InputError::ReadFile(job_dir.clone(), e))?;
+
+        let return_path = entry.path().join("return.p");
+        if !return_path.is_file() {
+            continue;
+        }
+
+        let minion = entry.file_name().to_string_lossy().into_owned();
+        trace!("reading return of minion {}", minion);
+
+        // older versions of salt only stored the return of the function so it
+        // is wrapped like the local_cache returner does when reading it
+        let ret = match read_serialized(&return_path)? {
+            Value::Object(ret) if ret.contains_key("return") => Value::Object(ret),
+            ret => serde_json::json!({ "return": ret }),
+        };
+
+        minions.insert(minion, ret);
+    }
+
+    let load_path = job_dir.join(".load.p");
+    let load = if load_path.is_file() {
+        Some(read_serialized(&load_path)?)
+    } else {
+        None
+    };
+
+    let minions_path = job_dir.join(".minions.p");
+    let targeted = if minions_path.is_file() {
+        read_serialized(&minions_path)?.as_array().map(Vec::len)
+    } else {
+        None
+    };
+
+    let field = |name: &str| {
+        load.as_ref()
+            .and_then(|load| load.get(name))
+            .map(value_to_string)
+    };
+
+    let job = Job {
+        jid: Some(jid.to_string()),
+        function: field("fun"),
+        arguments: load
+            .as_ref()
+            .and_then(|load| load.get("arg"))
+            .and_then(Value::as_array)
+            .map(|arguments| {
+                arguments
+                    .iter()
+                    .map(value_to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+        target: field("tgt"),
+        target_type: field("tgt_type"),
+        user: field("user"),
+        start_time: jid_to_time(jid),
+        minions: targeted,
+    };
+
+    let metadata = Metadata {
+        job: Some(job),
+        ..Metadata::default()
+    };
+
+    Ok((Value::Object(minions), metadata))
+}
+
+/// Find the directory of the job with the given jid by looking at the jid
+/// files of all jobs in the cache.
+fn find_job_dir(cache_dir: &Path, jid: &str) -> Result<PathBuf, InputError> {
+    let read_dir = |path: &Path| {
+        std::fs::read_dir(path).map_err(|e| InputError::ReadFile(path.to_path_buf(), e))
+    };
+
+    for prefix in read_dir(cache_dir)? {
+        let prefix = prefix.map_err(|e| InputError::ReadFile(cache_dir.to_path_buf(), e))?;
+        if !prefix.path().is_dir() {
+            continue;
+        }
+
+        for job_dir in read_dir(&prefix.path())? {
+            let job_dir = job_dir.map_err(|e| InputError::ReadFile(prefix.path(), e))?;
+
+            let jid_path = job_dir.path().join("jid");
+            if !jid_path.is_file() {
+                continue;
+            }
+
+            let content = std::fs::read_to_string(&jid_path)
+                .map_err(|e| InputError::ReadFile(jid_path.clone(), e))?;
+            if content.trim() == jid {
+                return Ok(job_dir.path());
+            }
+        }
+    }
+
+    Err(InputError::JobNotFound(cache_dir.join(jid)))
+}
+
+/// Read a file from the job cache that was written with either the json or the
+/// msgpack serializer.
+fn read_serialized(path: &Path) -> Result<Value, InputError> {
+    let data = std::fs::read(path).map_err(|e| InputError::ReadFile(path.to_path_buf(), e))?;
+
+    if let Ok(value) = serde_json::from_slice(&data) {
+        return Ok(value);
+    }
+
+    rmpv::decode::read_value(&mut data.as_slice())
+        .map(msgpack_to_json)
+        .map_err(|e| InputError::InvalidMsgpack(path.to_path_buf(), e))
+}
+
+/// Convert a msgpack value to the json value get_results works with. Keys of
+/// maps that are not strings will be converted to strings.
+fn msgpack_to_json(value: rmpv::Value) -> Value {
+    match value {
+        rmpv::Value::Nil => Value::Null,
+        rmpv::Value::Boolean(b) => Value::Bool(b),
+        rmpv::Value::Integer(i) => {
+            if let Some(u) = i.as_u64() {
+                Value::from(u)
+            } else {
+                i.as_i64().map(Value::from).unwrap_or(Value::Null)
+            }
+        }
+        rmpv::Value::F32(f) => Value::from(f64::from(f)),
+        rmpv::Value::F64(f) => Value::from(f),
+        rmpv::Value::String(s) => match s.into_str() {
+            Some(s) => Value::String(s),
+            None => Value::Null,
+        },
+        rmpv::Value::Binary(b) | rmpv::Value::Ext(_, b) => {
+            Value::String(String::from_utf8_lossy(&b).into_owned())
+        }
+        rmpv::Value::Array(array) => Value::Array(array.into_iter().map(msgpack_to_json).collect()),
+        rmpv::Value::Map(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let key = match msgpack_to_json(key) {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, msgpack_to_json(value))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a jid like 20170719112904786325 to the time format salt uses for
+/// the start time of jobs.
+fn jid_to_time(jid: &str) -> Option<String> {
+    if jid.len() != 20 || !jid.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    chrono::NaiveDateTime::parse_from_str(&jid[..14], "%Y%m%d%H%M%S")
+        .ok()
+        .map(|time| format!("{}.{}", time.format("%Y, %b %d %H:%M:%S"), &jid[14..]))
+}
+
+/// Merge the per minion objects of multiple documents into one object.
+fn merge_documents(mut documents: Vec<(usize, Value)>) -> Result<Value, InputError> {
+    // keep a single document as it is so get_results can complain about it
+    if documents.len() == 1 {
+        let (_, document) = documents.remove(0);
+        return Ok(document);
+    }
+
+    let mut merged = serde_json::Map::new();
+    for (line_number, document) in documents {
+        match document {
+            Value::Object(minions) => {
+                for (minion, values) in minions {
+                    if merged.contains_key(&minion) {
+                        warn!(
+                            "minion {} is in the input more than once, using the last result",
+                            minion
+                        );
+                    }
+                    merged.insert(minion, values);
+                }
+            }
+            _ => return Err(InputError::DocumentNotAnObject(line_number)),
+        }
+    }
+
+    Ok(Value::Object(merged))
 }
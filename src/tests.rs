@@ -1,5 +1,29 @@
+use crate::Filter;
+use regex::Regex;
+
+/// Convert a list of host names for comparisons with results.
+fn hosts(hosts: &[&str]) -> Vec<String> {
+    hosts.iter().map(ToString::to_string).collect()
+}
+
+/// Get a filter that does not remove any results.
+fn filter() -> Filter {
+    Filter {
+        command: Regex::new(".*").unwrap(),
+        failed: false,
+        outcomes: Vec::new(),
+        output: Regex::new(".*").unwrap(),
+        result: Regex::new(".*").unwrap(),
+        succeeded: false,
+        unchanged: false,
+    }
+}
+
 mod test_retcode {
-    use crate::Retcode;
+    use crate::{
+        FailureCategory,
+        Retcode,
+    };
 
     #[test]
     fn from_success() {
@@ -8,17 +32,32 @@ mod test_retcode {
 
     #[test]
     fn from_failure() {
-        for i in 1..10 {
-            assert_eq!(Retcode::Failure, i.into())
+        for i in 1..30 {
+            let category = match i {
+                2 => FailureCategory::StateFailed,
+                11 | 20 => FailureCategory::CompileError,
+                _ => FailureCategory::Error,
+            };
+
+            assert_eq!(Retcode::Failure(Some(i), category), i.into())
         }
     }
+
+    #[test]
+    fn display() {
+        assert_eq!(Retcode::from(2).to_string(), "Failure (2, state failed)");
+        assert_eq!(Retcode::default().to_string(), "Failure (no return code)");
+    }
 }
 
 mod test_get_results {
     use crate::{
         cleanup_input_data,
         get_results,
+        FailureCategory,
+        FailurePattern,
         MinionResult,
+        ResultOptions,
         Retcode,
     };
     use log::trace;
@@ -30,9 +69,9 @@ mod test_get_results {
     fn value_not_an_object() {
         let value = Value::default();
 
-        match get_results(&value, DataMap::default()) {
+        match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(_) => {}
-            Err(e) => panic!(format!("{}", e)),
+            Err(e) => panic!("{}", e),
         }
     }
 
@@ -40,7 +79,7 @@ mod test_get_results {
     fn empty_results() {
         let value: Value = serde_json::from_str("{}").unwrap();
 
-        let got = match get_results(&value, DataMap::default()) {
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
@@ -55,25 +94,31 @@ mod test_get_results {
     #[test]
     fn only_failed_hosts() {
         let input = include_str!("../testdata/only_failed_hosts.json");
-        let (input, _) = cleanup_input_data(input);
+        let (input, _) = cleanup_input_data(input, &FailurePattern::builtin());
 
         let value: Value =
             serde_json::from_str(input.as_str()).expect("can not parse input to json");
 
         let mut failed_hosts = DataMap::default();
-        failed_hosts.insert("minion_fail_1".into(), "");
-        failed_hosts.insert("minion_fail_1".into(), "");
+        failed_hosts.insert(
+            "minion_fail_1".into(),
+            (FailureCategory::NotResponded, String::new()),
+        );
+        failed_hosts.insert(
+            "minion_fail_1".into(),
+            (FailureCategory::NotResponded, String::new()),
+        );
 
-        let got = match get_results(&value, failed_hosts.clone()) {
+        let got = match get_results(&value, failed_hosts.clone(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
         let mut expected = Vec::new();
-        for (host, message) in failed_hosts {
+        for (host, (category, message)) in failed_hosts {
             expected.push(MinionResult {
-                host: host,
-                retcode: Retcode::Failure,
-                output: Some(message.into()),
+                host,
+                retcode: Retcode::Failure(None, category),
+                output: Some(message),
                 ..MinionResult::default()
             });
         }
@@ -87,25 +132,31 @@ mod test_get_results {
     #[test]
     fn duplicate_keys_hosts() {
         let input = include_str!("../testdata/duplicate_keys_hosts.json");
-        let (input, _) = cleanup_input_data(input);
+        let (input, _) = cleanup_input_data(input, &FailurePattern::builtin());
 
         let value: Value =
             serde_json::from_str(input.as_str()).expect("can not parse input to json");
 
         let mut failed_hosts = DataMap::default();
-        failed_hosts.insert("minion_fail_1".into(), "");
-        failed_hosts.insert("minion_fail_2".into(), "");
+        failed_hosts.insert(
+            "minion_fail_1".into(),
+            (FailureCategory::DuplicateKey, String::new()),
+        );
+        failed_hosts.insert(
+            "minion_fail_2".into(),
+            (FailureCategory::DuplicateKey, String::new()),
+        );
 
-        let got = match get_results(&value, failed_hosts.clone()) {
+        let got = match get_results(&value, failed_hosts.clone(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
 
         let mut expected = Vec::new();
-        for (host, message) in failed_hosts {
+        for (host, (category, message)) in failed_hosts {
             expected.push(MinionResult {
-                host: host,
-                retcode: Retcode::Failure,
+                host,
+                retcode: Retcode::Failure(None, category),
                 output: Some(message.to_string()),
                 ..MinionResult::default()
             });
@@ -122,18 +173,17 @@ mod test_get_results {
         let input = include_str!("../testdata/array.json");
         let value: Value = serde_json::from_str(input).unwrap();
 
-        let got = match get_results(&value, DataMap::default()) {
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
 
-        let mut expected = Vec::new();
-        expected.push(MinionResult {
+        let expected = vec![MinionResult {
             host: "minion".to_string(),
-            retcode: Retcode::Failure,
+            retcode: Retcode::from(1),
             result: Some("line1\nline2\nline3".to_string()),
             ..MinionResult::default()
-        });
+        }];
 
         trace!("got: {:#?}", got);
         trace!("expected: {:#?}", expected);
@@ -142,15 +192,85 @@ mod test_get_results {
     }
 
     #[test]
-    #[should_panic(expected = "can not convert the array value to a string")]
     fn array_weird() {
         let input = include_str!("../testdata/array_weird.json");
         let value: Value = serde_json::from_str(input).unwrap();
 
-        match get_results(&value, DataMap::default()) {
-            Ok(_) => {}
-            Err(e) => panic!(e),
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![MinionResult {
+            host: "minion".to_string(),
+            retcode: Retcode::from(1),
+            result: Some("1\n2\n3\n4".to_string()),
+            ..MinionResult::default()
+        }];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn number_as_ret() {
+        let input = include_str!("../testdata/number_as_ret.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
         };
+
+        let expected = vec![MinionResult {
+            host: "null".to_string(),
+            retcode: Retcode::Failure(None, FailureCategory::NoReturnCode),
+            result: Some("0".to_string()),
+            ..MinionResult::default()
+        }];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn all_value_types() {
+        let input = include_str!("../testdata/value_types.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got: Vec<(String, Option<String>, Option<String>)> =
+            match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+                Ok(r) => r
+                    .into_iter()
+                    .map(|result| (result.host, result.command, result.result))
+                    .collect(),
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let expected = vec![
+            ("float".to_string(), None, Some("1.5".to_string())),
+            (
+                "grains".to_string(),
+                Some("ipv4".to_string()),
+                Some("10.0.0.1\n127.0.0.1".to_string()),
+            ),
+            (
+                "grains".to_string(),
+                Some("locale_info".to_string()),
+                Some("defaultencoding: UTF-8\ndetectedencoding: utf-8".to_string()),
+            ),
+            (
+                "grains".to_string(),
+                Some("os".to_string()),
+                Some("Ubuntu".to_string()),
+            ),
+            (
+                "nested_array".to_string(),
+                None,
+                Some("- line1\n-\n  - 1\n  - 2\n-\n  key: value".to_string()),
+            ),
+            ("null".to_string(), None, Some("null".to_string())),
+        ];
+
+        assert_eq!(got, expected);
     }
 
     #[test]
@@ -158,24 +278,25 @@ mod test_get_results {
         let input = include_str!("../testdata/bool.json");
         let value: Value = serde_json::from_str(input).unwrap();
 
-        let got = match get_results(&value, DataMap::default()) {
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
 
-        let mut expected = Vec::new();
-        expected.push(MinionResult {
-            host: "minion".to_string(),
-            retcode: Retcode::Success,
-            result: Some("true".to_string()),
-            ..MinionResult::default()
-        });
-        expected.push(MinionResult {
-            host: "minion_fail".to_string(),
-            retcode: Retcode::Failure,
-            result: Some("false".to_string()),
-            ..MinionResult::default()
-        });
+        let mut expected = vec![
+            MinionResult {
+                host: "minion".to_string(),
+                retcode: Retcode::Success,
+                result: Some("true".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion_fail".to_string(),
+                retcode: Retcode::from(1),
+                result: Some("false".to_string()),
+                ..MinionResult::default()
+            },
+        ];
         expected.sort();
 
         trace!("got: {:#?}", got);
@@ -189,18 +310,17 @@ mod test_get_results {
         let input = include_str!("../testdata/no_ret_array.json");
         let value: Value = serde_json::from_str(input).unwrap();
 
-        let got = match get_results(&value, DataMap::default()) {
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
 
-        let mut expected = Vec::new();
-        expected.push(MinionResult {
+        let expected = vec![MinionResult {
             host: "minion".to_string(),
-            retcode: Retcode::Failure,
+            retcode: Retcode::Failure(None, FailureCategory::NoReturnCode),
             result: Some("line1\nline2\nline3".to_string()),
             ..MinionResult::default()
-        });
+        }];
 
         trace!("got: {:#?}", got);
         trace!("expected: {:#?}", expected);
@@ -213,33 +333,34 @@ mod test_get_results {
         let input = include_str!("../testdata/old_new_values_in_ret.json");
         let value: Value = serde_json::from_str(input).unwrap();
 
-        let got = match get_results(&value, DataMap::default()) {
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
             Ok(r) => r,
             Err(e) => panic!("unexpected error: {}", e),
         };
 
-        let mut expected = Vec::new();
-        expected.push(MinionResult {
-            host: "minion1".to_string(),
-            retcode: Retcode::Success,
-            output: Some("Old: version1\nNew: \n".to_string()),
-            command: Some("package".into()),
-            ..MinionResult::default()
-        });
-        expected.push(MinionResult {
-            host: "minion2".to_string(),
-            retcode: Retcode::Success,
-            output: Some("Old: version1\nNew: \n".to_string()),
-            command: Some("package".into()),
-            ..MinionResult::default()
-        });
-        expected.push(MinionResult {
-            host: "minion3".to_string(),
-            retcode: Retcode::Success,
-            output: Some("Old: version2\nNew: \n".to_string()),
-            command: Some("package".into()),
-            ..MinionResult::default()
-        });
+        let expected = vec![
+            MinionResult {
+                host: "minion1".to_string(),
+                retcode: Retcode::Success,
+                output: Some("Old: version1\nNew: \n".to_string()),
+                command: Some("package".into()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion2".to_string(),
+                retcode: Retcode::Success,
+                output: Some("Old: version1\nNew: \n".to_string()),
+                command: Some("package".into()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion3".to_string(),
+                retcode: Retcode::Success,
+                output: Some("Old: version2\nNew: \n".to_string()),
+                command: Some("package".into()),
+                ..MinionResult::default()
+            },
+        ];
 
         trace!("got: {:#?}", got);
         trace!("expected: {:#?}", expected);
@@ -247,3 +368,2034 @@ mod test_get_results {
         assert_eq!(got, expected);
     }
 }
+
+mod test_parse_input_data {
+    use crate::{
+        get_results,
+        parse_input_data,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+    };
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn batch() {
+        let input = include_str!("../testdata/batch.json");
+
+        let (value, metadata) = match parse_input_data(input) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![
+            MinionResult {
+                host: "minion1".to_string(),
+                retcode: Retcode::Success,
+                result: Some("true".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion2".to_string(),
+                retcode: Retcode::Success,
+                result: Some("true".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion3".to_string(),
+                retcode: Retcode::from(1),
+                result: Some("false".to_string()),
+                ..MinionResult::default()
+            },
+        ];
+
+        assert_eq!(got, expected);
+        assert_eq!(
+            metadata.messages,
+            vec![
+                "Executing run on ['minion1', 'minion2']",
+                "Executing run on ['minion3']",
+                "[WARNING ] Minion minion3 is using an outdated version of salt",
+                "ERROR: Minions returned with non-zero exit code",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "input does not contain any json document")]
+    fn empty() {
+        if let Err(e) = parse_input_data("\n\n") {
+            panic!("{}", e)
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected line 2 in input: this is not json")]
+    fn unexpected_line() {
+        if let Err(e) = parse_input_data("{}\nthis is not json\n{}") {
+            panic!("{}", e)
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "json document starting at line 2 is not an object")]
+    fn document_not_an_object() {
+        if let Err(e) = parse_input_data("{}\n[1, 2]") {
+            panic!("{}", e)
+        }
+    }
+}
+
+mod test_parse_input {
+    use crate::{
+        parse_input,
+        InputError,
+        InputFormat,
+    };
+    use serde_json::Value;
+
+    #[test]
+    fn yaml_same_as_json() {
+        let json: Value = serde_json::from_str(include_str!("../testdata/command.json")).unwrap();
+
+        for format in &[InputFormat::Auto, InputFormat::Yaml] {
+            let (got, _) =
+                match parse_input(include_str!("../testdata/command.yaml"), *format, None) {
+                    Ok(r) => r,
+                    Err(e) => panic!("unexpected error: {}", e),
+                };
+
+            assert_eq!(got, json);
+        }
+    }
+
+    #[test]
+    fn list_job_jid() {
+        let input = include_str!("../testdata/list_job.json");
+
+        if let Err(e) = parse_input(input, InputFormat::Auto, Some("20170719112904786325")) {
+            panic!("unexpected error: {}", e);
+        }
+
+        match parse_input(input, InputFormat::Auto, Some("20170719112904786326")) {
+            Err(InputError::JidNotInInput(jid)) => assert_eq!(jid, "20170719112904786326"),
+            Ok(_) => panic!("unexpected result for a different jid"),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn yaml_batch() {
+        let input = include_str!("../testdata/batch.yaml");
+
+        let (got, metadata) = match parse_input(input, InputFormat::Auto, None) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = serde_json::json!({
+            "minion1": { "retcode": 0, "ret": true },
+            "minion2": { "retcode": 0, "ret": { "1": "line1", "2": "line2" } },
+            "minion3": { "retcode": 1, "ret": false },
+        });
+
+        assert_eq!(got, expected);
+        assert_eq!(
+            metadata.messages,
+            vec![
+                "Executing run on ['minion1', 'minion2']",
+                "Executing run on ['minion3']",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_detected() {
+        let input = include_str!("../testdata/batch.json");
+
+        if let Err(e) = parse_input(input, InputFormat::Auto, None) {
+            panic!("unexpected error: {}", e)
+        }
+    }
+}
+
+mod test_jobs_runner {
+    use crate::{
+        get_job,
+        get_results,
+        FailureCategory,
+        Job,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+        StateResult,
+        StateReturn,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    fn nginx_running(host: &str, retcode: Retcode) -> MinionResult {
+        let comment = "The service nginx is already running".to_string();
+
+        MinionResult {
+            host: host.to_string(),
+            command: Some("service_|-nginx_|-nginx_|-running".to_string()),
+            retcode,
+            state: Some(StateReturn {
+                comment: Some(comment),
+                ..StateReturn::default()
+            }),
+            ..MinionResult::default()
+        }
+    }
+
+    #[test]
+    fn list_job() {
+        let input = include_str!("../testdata/list_job.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![
+            nginx_running("minion1", Retcode::Success),
+            nginx_running("minion2", Retcode::Success),
+        ];
+
+        assert_eq!(got, expected);
+
+        let expected = Job {
+            jid: Some("20170719112904786325".to_string()),
+            function: Some("state.apply".to_string()),
+            arguments: Some(r#"nginx, {"__kwarg__":true,"test":true}"#.to_string()),
+            target: Some("web*".to_string()),
+            target_type: Some("glob".to_string()),
+            user: Some("root".to_string()),
+            start_time: Some("2017, Jul 19 11:29:04.786325".to_string()),
+            minions: Some(3),
+        };
+
+        assert_eq!(get_job(&value), Some(expected));
+    }
+
+    #[test]
+    fn lookup_jid() {
+        let input = include_str!("../testdata/lookup_jid.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        // the return codes are derived from the results of the states
+        let state_result = |host: &str, retcode: Retcode, result: StateResult| {
+            let mut expected = nginx_running(host, retcode);
+            if let Some(ref mut state) = expected.state {
+                state.result = Some(result);
+            }
+            expected
+        };
+
+        let expected = vec![
+            state_result("minion1", Retcode::Success, StateResult::True),
+            state_result(
+                "minion2",
+                Retcode::Failure(None, FailureCategory::StateFailed),
+                StateResult::False,
+            ),
+        ];
+
+        assert_eq!(got, expected);
+        assert_eq!(get_job(&value), None);
+    }
+}
+
+mod test_events {
+    use crate::{
+        get_results,
+        parse_input,
+        InputFormat,
+        Job,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+    };
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn returns() {
+        let input = include_str!("../testdata/events.txt");
+
+        let (value, metadata) = match parse_input(input, InputFormat::Auto, None) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![
+            MinionResult {
+                host: "minion1".to_string(),
+                command: Some("cmd.run".to_string()),
+                result: Some("up 10 days".to_string()),
+                retcode: Retcode::Success,
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion2".to_string(),
+                command: Some("cmd.run".to_string()),
+                result: Some("uptime: command not found".to_string()),
+                retcode: Retcode::from(1),
+                ..MinionResult::default()
+            },
+        ];
+
+        assert_eq!(got, expected);
+
+        let expected = Job {
+            jid: Some("20170719112904786325".to_string()),
+            function: Some("cmd.run".to_string()),
+            start_time: Some("2017, Jul 19 11:29:04.786325".to_string()),
+            ..Job::default()
+        };
+
+        assert_eq!(metadata.job, Some(expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected line 2 in input: no tab here")]
+    fn line_without_tag() {
+        let input = "salt/job/1/ret/minion\t{}\nno tab here";
+
+        if let Err(e) = parse_input(input, InputFormat::Event, None) {
+            panic!("{}", e)
+        }
+    }
+}
+
+mod test_job_cache {
+    use crate::{
+        get_results,
+        read_job_cache,
+        FailureCategory,
+        Job,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+    };
+    use std::{
+        collections::BTreeMap as DataMap,
+        path::Path,
+    };
+
+    const CACHE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/job_cache");
+
+    #[test]
+    fn json_and_msgpack() {
+        let (value, metadata) = match read_job_cache(Path::new(CACHE_DIR), "20170719112904786325") {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![
+            MinionResult {
+                host: "minion1".to_string(),
+                retcode: Retcode::Success,
+                result: Some("true".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion2".to_string(),
+                retcode: Retcode::from(1),
+                result: Some("false".to_string()),
+                ..MinionResult::default()
+            },
+        ];
+
+        assert_eq!(got, expected);
+
+        let expected = Job {
+            jid: Some("20170719112904786325".to_string()),
+            function: Some("test.ping".to_string()),
+            arguments: Some(String::new()),
+            target: Some("*".to_string()),
+            target_type: Some("glob".to_string()),
+            user: Some("root".to_string()),
+            start_time: Some("2017, Jul 19 11:29:04.786325".to_string()),
+            minions: Some(3),
+        };
+
+        assert_eq!(metadata.job, Some(expected));
+    }
+
+    #[test]
+    fn only_return() {
+        let (value, _) = match read_job_cache(Path::new(CACHE_DIR), "20160503093012345678") {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, Retcode, Option<String>)> =
+            match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+                Ok(r) => r
+                    .into_iter()
+                    .map(|result| {
+                        let text = result.result_text().map(ToString::to_string);
+                        (result.host, result.retcode, text)
+                    })
+                    .collect(),
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let expected = vec![
+            (
+                "minion1".to_string(),
+                Retcode::Success,
+                Some("The service nginx is already running".to_string()),
+            ),
+            (
+                "minion2".to_string(),
+                Retcode::Failure(None, FailureCategory::NoReturnCode),
+                Some("pong".to_string()),
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "can not find job")]
+    fn job_not_found() {
+        if let Err(e) = read_job_cache(Path::new(CACHE_DIR), "20000101000000000000") {
+            panic!("{}", e)
+        }
+    }
+}
+
+mod test_rawfile {
+    use crate::{
+        get_results,
+        parse_input,
+        InputFormat,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+        StateReturn,
+    };
+    use serde_json::{
+        json,
+        Value,
+    };
+    use std::collections::BTreeMap as DataMap;
+
+    fn nginx(host: &str, comment: &str, changes: Value) -> MinionResult {
+        let changes = match changes["nginx"] {
+            Value::Bool(b) => Some(format!("nginx: {}\n", b)),
+            _ => None,
+        };
+
+        MinionResult {
+            host: host.to_string(),
+            command: Some("service_|-nginx_|-nginx_|-running".to_string()),
+            retcode: Retcode::Success,
+            state: Some(StateReturn {
+                comment: Some(comment.to_string()),
+                changes,
+                ..StateReturn::default()
+            }),
+            ..MinionResult::default()
+        }
+    }
+
+    #[test]
+    fn latest_job() {
+        let input = include_str!("../testdata/rawfile.json");
+
+        let (value, metadata) = match parse_input(input, InputFormat::Auto, None) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = vec![
+            nginx("minion1", "Started service nginx", json!({"nginx": true})),
+            nginx("minion2", "Started service nginx", json!({"nginx": true})),
+        ];
+
+        assert_eq!(got, expected);
+        assert_eq!(
+            metadata.job.and_then(|job| job.jid),
+            Some("20170719112904786325".to_string())
+        );
+    }
+
+    #[test]
+    fn selected_job() {
+        let input = include_str!("../testdata/rawfile.json");
+
+        let (value, _) =
+            match parse_input(input, InputFormat::Rawfile, Some("20170718112904786325")) {
+                Ok(r) => r,
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert_eq!(
+            got,
+            vec![nginx(
+                "minion1",
+                "The service nginx is already running",
+                json!({})
+            )]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "input does not contain returns of job 20000101000000000000")]
+    fn unknown_job() {
+        let input = include_str!("../testdata/rawfile.json");
+
+        if let Err(e) = parse_input(input, InputFormat::Rawfile, Some("20000101000000000000")) {
+            panic!("{}", e)
+        }
+    }
+}
+
+mod test_state_return {
+    use crate::{
+        get_compressed,
+        get_results,
+        CompressOptions,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+        StateResult,
+        StateReturn,
+    };
+    use serde_json::{
+        json,
+        Value,
+    };
+    use std::{
+        collections::BTreeMap as DataMap,
+        time::Duration,
+    };
+
+    #[test]
+    fn all_fields() {
+        let input = include_str!("../testdata/state_return.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let expected = StateReturn {
+            id: Some("nginx".to_string()),
+            name: Some("nginx".to_string()),
+            sls: Some("webserver.nginx".to_string()),
+            run_num: Some(3),
+            result: Some(StateResult::True),
+            comment: Some("The following packages were installed/updated: nginx".to_string()),
+            changes: Some("nginx:\n  new: 1.18.0\n  old: \n".to_string()),
+            warnings: vec!["'refresh' is deprecated".to_string()],
+            duration: Some(Duration::from_millis(1500)),
+            start_time: Some("11:29:04.786325".to_string()),
+        };
+
+        assert_eq!(got[0].state, Some(expected));
+        assert_eq!(got[0].retcode, Retcode::Success);
+    }
+
+    #[test]
+    fn compress_ignores_per_host_fields() {
+        let input = include_str!("../testdata/state_return.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let compressed = get_compressed(results, &CompressOptions::default());
+        let hosts: Vec<Vec<String>> = compressed
+            .values()
+            .map(|results| results.iter().map(|result| result.host.clone()).collect())
+            .collect();
+
+        assert_eq!(hosts, vec![vec!["minion1", "minion2"]]);
+
+        let durations: Vec<Option<Duration>> = compressed
+            .values()
+            .flat_map(|results| {
+                results
+                    .iter()
+                    .map(|result| result.state.clone().unwrap().duration)
+            })
+            .collect();
+
+        assert_eq!(
+            durations,
+            vec![
+                Some(Duration::from_millis(1500)),
+                Some(Duration::from_millis(250))
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "state field result has an invalid value")]
+    fn invalid_result() {
+        let value = json!({"minion": {"retcode": 0, "ret": {"state": {"result": "yes"}}}});
+
+        if let Err(e) = get_results(
+            &value,
+            DataMap::default(),
+            &ResultOptions {
+                strict: true,
+                ..ResultOptions::default()
+            },
+        ) {
+            panic!("{}", e)
+        }
+    }
+
+    #[test]
+    fn not_a_state() {
+        let input = include_str!("../testdata/old_new_values_in_ret.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert!(got
+            .iter()
+            .all(|result: &MinionResult| result.state.is_none()));
+    }
+}
+
+mod test_outcome {
+    use crate::{
+        get_results,
+        MinionResult,
+        Outcome,
+        ResultOptions,
+        Retcode,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn states() {
+        let input = include_str!("../testdata/test_run.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got: DataMap<String, Outcome> =
+            match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+                Ok(r) => r
+                    .iter()
+                    .map(|result| (result.command.clone().unwrap(), result.outcome()))
+                    .collect(),
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let mut expected = DataMap::new();
+        expected.insert(
+            "file_|-config_|-/etc/app.conf_|-managed".to_string(),
+            Outcome::WouldChange,
+        );
+        expected.insert(
+            "pkg_|-app_|-app_|-installed".to_string(),
+            Outcome::Succeeded,
+        );
+        expected.insert(
+            "service_|-app_|-app_|-running".to_string(),
+            Outcome::Changed,
+        );
+        expected.insert("cmd_|-migrate_|-migrate_|-run".to_string(), Outcome::Failed);
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn states_without_result() {
+        let input = include_str!("../testdata/command.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got: DataMap<String, Outcome> =
+            match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+                Ok(r) => r
+                    .iter()
+                    .map(|result| (result.command.clone().unwrap(), result.outcome()))
+                    .collect(),
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let mut expected = DataMap::new();
+        expected.insert("command_with_changes".to_string(), Outcome::Changed);
+        expected.insert("command_with_changes2".to_string(), Outcome::Changed);
+        expected.insert("command_with_no_comment".to_string(), Outcome::Succeeded);
+        expected.insert("command_without_changes".to_string(), Outcome::Succeeded);
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn not_a_state() {
+        let succeeded = MinionResult {
+            retcode: Retcode::Success,
+            ..MinionResult::default()
+        };
+        let failed = MinionResult {
+            retcode: Retcode::from(1),
+            ..MinionResult::default()
+        };
+
+        assert_eq!(succeeded.outcome(), Outcome::Succeeded);
+        assert_eq!(failed.outcome(), Outcome::Failed);
+    }
+}
+
+mod test_failure_category {
+    use crate::{
+        get_compressed,
+        get_results,
+        CompressOptions,
+        FailureCategory,
+        ResultOptions,
+        Retcode,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn already_running() {
+        let input = include_str!("../testdata/highstate_is_already_running.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        for result in got {
+            assert_eq!(
+                result.retcode,
+                Retcode::Failure(None, FailureCategory::AlreadyRunning)
+            );
+        }
+    }
+
+    #[test]
+    fn code_is_part_of_key() {
+        let value = serde_json::json!({
+            "minion1": { "retcode": 1, "ret": "failed" },
+            "minion2": { "retcode": 2, "ret": "failed" },
+            "minion3": { "ret": "failed" },
+        });
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<Retcode> = get_compressed(results, &CompressOptions::default())
+            .into_keys()
+            .map(|key| key.retcode)
+            .collect();
+
+        let expected = vec![
+            Retcode::Failure(None, FailureCategory::NoReturnCode),
+            Retcode::Failure(Some(1), FailureCategory::Error),
+            Retcode::Failure(Some(2), FailureCategory::StateFailed),
+        ];
+
+        assert_eq!(got, expected);
+    }
+}
+
+mod test_render_changes {
+    use crate::{
+        get_results,
+        render_changes,
+        ResultOptions,
+    };
+    use serde_json::{
+        json,
+        Value,
+    };
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn structured_changes() {
+        let input = include_str!("../testdata/changes.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got: DataMap<String, Option<String>> =
+            match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+                Ok(r) => r
+                    .into_iter()
+                    .map(|result| {
+                        let output = result.output_text().map(ToString::to_string);
+                        (result.command.unwrap(), output)
+                    })
+                    .collect(),
+                Err(e) => panic!("unexpected error: {}", e),
+            };
+
+        let mut expected = DataMap::new();
+        expected.insert(
+            "cmd_|-migrate_|-migrate_|-run".to_string(),
+            Some("pid: 4242\nretcode: 0\nstderr: \nstdout:\n  line1\n  line2\n".to_string()),
+        );
+        expected.insert(
+            "file_|-config_|-/etc/app.conf_|-managed".to_string(),
+            Some("--- \n+++ \n@@ -1 +1 @@\n-old\n+new\nmode: 0644\nuser: app\n".to_string()),
+        );
+        expected.insert(
+            "pkg_|-packages_|-packages_|-installed".to_string(),
+            Some(
+                "curl:\n  new: 7.68.0\n  old: 7.58.0\nnginx:\n  new: 1.18.0\n  old: \n".to_string(),
+            ),
+        );
+        expected.insert(
+            "user_|-app_|-app_|-present".to_string(),
+            Some("groups:\n  - app\n  - docker\nshell: /bin/bash\n".to_string()),
+        );
+        expected.insert("service_|-app_|-app_|-running".to_string(), None);
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn only_diff() {
+        let got = render_changes(&json!({"diff": "+ added\n- removed"}));
+
+        assert_eq!(got, Some("+ added\n- removed".to_string()));
+    }
+
+    #[test]
+    fn weird_diff() {
+        let input = include_str!("../testdata/command_weird.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got: Vec<Option<String>> = match get_results(
+            &value,
+            DataMap::default(),
+            &ResultOptions {
+                strict: true,
+                ..ResultOptions::default()
+            },
+        ) {
+            Ok(r) => r
+                .iter()
+                .map(|result| result.output_text().map(ToString::to_string))
+                .collect(),
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert_eq!(got, vec![Some("diff: 0\n".to_string())]);
+    }
+}
+
+mod test_lenient {
+    use crate::{
+        get_results,
+        FailureCategory,
+        MinionResult,
+        ResultOptions,
+        Retcode,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn unparsable_hosts() {
+        let input = include_str!("../testdata/unparsable_hosts.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let got = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let unparsable = Retcode::Failure(None, FailureCategory::Unparsable);
+        let expected = vec![
+            MinionResult {
+                host: "minion".to_string(),
+                retcode: Retcode::Success,
+                result: Some("true".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion_invalid_result".to_string(),
+                retcode: unparsable.clone(),
+                output: Some("state field result has an invalid value".to_string()),
+                ..MinionResult::default()
+            },
+            MinionResult {
+                host: "minion_invalid_retcode".to_string(),
+                retcode: unparsable,
+                output: Some("returncode is not a number".to_string()),
+                ..MinionResult::default()
+            },
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "state field result has an invalid value")]
+    fn strict() {
+        let input = include_str!("../testdata/unparsable_hosts.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let options = ResultOptions {
+            strict: true,
+            ..ResultOptions::default()
+        };
+
+        if let Err(e) = get_results(&value, DataMap::default(), &options) {
+            panic!("{}", e)
+        }
+    }
+}
+
+mod test_generic {
+    use super::{
+        filter,
+        hosts,
+    };
+    use crate::{
+        get_compressed,
+        get_filtered,
+        get_results,
+        CompressOptions,
+        Filter,
+        ResultOptions,
+    };
+    use regex::Regex;
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn group_by_path() {
+        let input = include_str!("../testdata/grains.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let options = ResultOptions {
+            generic: true,
+            ..ResultOptions::default()
+        };
+
+        let results = match get_results(&value, DataMap::default(), &options) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, String, Vec<String>)> =
+            get_compressed(results, &CompressOptions::default())
+                .into_iter()
+                .map(|(key, results)| {
+                    (
+                        key.command.unwrap(),
+                        key.result.unwrap(),
+                        results.into_iter().map(|result| result.host).collect(),
+                    )
+                })
+                .collect();
+
+        let expected = vec![
+            (
+                "disks:0:name".to_string(),
+                "sda".to_string(),
+                hosts(&["minion1", "minion2", "minion3"]),
+            ),
+            (
+                "disks:0:size".to_string(),
+                "100".to_string(),
+                hosts(&["minion1", "minion2"]),
+            ),
+            (
+                "disks:0:size".to_string(),
+                "50".to_string(),
+                hosts(&["minion3"]),
+            ),
+            (
+                "ip_interfaces:eth0".to_string(),
+                "10.0.0.1".to_string(),
+                hosts(&["minion1"]),
+            ),
+            (
+                "ip_interfaces:eth0".to_string(),
+                "10.0.0.2".to_string(),
+                hosts(&["minion2"]),
+            ),
+            (
+                "ip_interfaces:eth0".to_string(),
+                "10.0.0.3".to_string(),
+                hosts(&["minion3"]),
+            ),
+            (
+                "os".to_string(),
+                "Ubuntu".to_string(),
+                hosts(&["minion1", "minion2", "minion3"]),
+            ),
+            (
+                "osrelease".to_string(),
+                "20.04".to_string(),
+                hosts(&["minion3"]),
+            ),
+            (
+                "osrelease".to_string(),
+                "22.04".to_string(),
+                hosts(&["minion1", "minion2"]),
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn filter_original_values() {
+        let value = serde_json::json!({
+            "web1": { "retcode": 0, "ret": { "name": "web1" } },
+            "web2": { "retcode": 0, "ret": { "name": "web2" } },
+        });
+
+        let options = ResultOptions {
+            generic: true,
+            ..ResultOptions::default()
+        };
+
+        let results = match get_results(&value, DataMap::default(), &options) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let options = CompressOptions {
+            substitute_minion: true,
+            ..CompressOptions::default()
+        };
+        let compressed = get_compressed(results, &options);
+        assert_eq!(compressed.len(), 1);
+
+        let filter = Filter {
+            result: Regex::new("^web1$").unwrap(),
+            ..filter()
+        };
+
+        let got: Vec<(Option<String>, Vec<String>)> = get_filtered(compressed, &filter)
+            .0
+            .into_iter()
+            .map(|(key, results)| {
+                (
+                    key.result,
+                    results.into_iter().map(|result| result.host).collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![(Some("{{minion}}".to_string()), hosts(&["web1"]))]
+        );
+    }
+}
+
+mod test_orchestrate {
+    use crate::{
+        get_compressed,
+        get_results,
+        CompressOptions,
+        FailureCategory,
+        ResultOptions,
+        Retcode,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn steps_in_order() {
+        let input = include_str!("../testdata/orchestrate.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, Vec<String>)> = get_compressed(results, &CompressOptions::default())
+            .into_iter()
+            .map(|(key, results)| {
+                (
+                    key.step.unwrap().name,
+                    results.into_iter().map(|result| result.host).collect(),
+                )
+            })
+            .collect();
+
+        let expected = vec![
+            (
+                "deploy_web".to_string(),
+                vec!["web1".to_string(), "web2".to_string()],
+            ),
+            ("restart_db".to_string(), vec!["db1".to_string()]),
+            ("notify".to_string(), vec!["master_master".to_string()]),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn failed_state_in_step() {
+        let input = r#"{"master": {"salt_|-deploy_|-deploy_|-state": {
+            "__id__": "deploy", "__run_num__": 0, "result": false, "comment": "",
+            "changes": {"ret": {
+                "web1": {"cmd_|-a_|-a_|-run": {"result": true, "comment": "", "changes": {}}},
+                "web2": {"cmd_|-a_|-a_|-run": {"result": false, "comment": "", "changes": {}}}
+            }}
+        }}}"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, bool)> = results
+            .into_iter()
+            .map(|result| (result.host, result.retcode == Retcode::Success))
+            .collect();
+
+        let expected = vec![("web1".to_string(), true), ("web2".to_string(), false)];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn invalid_master_step() {
+        let input = r#"{"master": {
+            "salt_|-deploy_|-deploy_|-state": {
+                "__id__": "deploy", "__run_num__": 0, "result": true, "comment": "",
+                "changes": {"ret": {
+                    "web1": {"cmd_|-a_|-a_|-run": {"result": true, "comment": "", "changes": {}}}
+                }}
+            },
+            "salt_|-notify_|-notify_|-runner": {
+                "__id__": "notify", "__run_num__": 1, "result": "weird", "comment": "",
+                "changes": {}
+            }
+        }}"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, Retcode)> = results
+            .into_iter()
+            .map(|result| (result.host, result.retcode))
+            .collect();
+
+        let expected = vec![
+            ("web1".to_string(), Retcode::Success),
+            (
+                "master".to_string(),
+                Retcode::Failure(None, FailureCategory::Unparsable),
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+}
+
+mod test_salt_ssh {
+    use crate::{
+        get_compressed,
+        get_results,
+        CompressOptions,
+        FailureCategory,
+        ResultOptions,
+        Retcode,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn return_and_stdout() {
+        let input = include_str!("../testdata/salt_ssh.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(Option<String>, Vec<String>)> =
+            get_compressed(results, &CompressOptions::default())
+                .into_iter()
+                .map(|(key, results)| {
+                    (
+                        key.command,
+                        results.into_iter().map(|result| result.host).collect(),
+                    )
+                })
+                .collect();
+
+        let expected = vec![
+            (None, vec!["web3".to_string()]),
+            (
+                Some("pkg_|-nginx_|-nginx_|-installed".to_string()),
+                vec!["web1".to_string(), "web2".to_string()],
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn stderr() {
+        let input =
+            r#"{"web1": {"return": "hello", "retcode": 1, "stderr": "warning", "stdout": ""}}"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(Option<String>, Option<String>, Option<String>)> = results
+            .into_iter()
+            .map(|result| {
+                assert_eq!(
+                    result.retcode,
+                    Retcode::Failure(Some(1), FailureCategory::Error)
+                );
+                (result.command, result.result, result.output)
+            })
+            .collect();
+
+        let expected = vec![
+            (None, Some("hello".to_string()), None),
+            (
+                Some("stderr".to_string()),
+                None,
+                Some("warning".to_string()),
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn cmd_run_all() {
+        let input =
+            r#"{"web1": {"pid": 1234, "retcode": 1, "stderr": "error", "stdout": "output"}}"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(Option<String>, Option<String>)> = results
+            .into_iter()
+            .map(|result| {
+                assert_eq!(
+                    result.retcode,
+                    Retcode::Failure(None, FailureCategory::NoReturnCode)
+                );
+                (result.command, result.result)
+            })
+            .collect();
+
+        let expected = vec![
+            (Some("pid".to_string()), Some("1234".to_string())),
+            (Some("retcode".to_string()), Some("1".to_string())),
+            (Some("stderr".to_string()), Some("error".to_string())),
+            (Some("stdout".to_string()), Some("output".to_string())),
+        ];
+
+        assert_eq!(got, expected);
+    }
+}
+
+mod test_failure_patterns {
+    use crate::{
+        cleanup_input_data,
+        get_results,
+        parse_input,
+        read_failure_patterns,
+        FailureCategory,
+        FailurePattern,
+        InputFormat,
+        ResultOptions,
+    };
+    use std::{
+        collections::BTreeMap as DataMap,
+        path::Path,
+    };
+
+    #[test]
+    fn lines_and_strings() {
+        let input = include_str!("../testdata/not_returned.txt");
+        let (input, failed_minions) = cleanup_input_data(input, &FailurePattern::builtin());
+
+        let value = match parse_input(&input, InputFormat::Auto, None) {
+            Ok((value, _)) => value,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let results = match get_results(&value, failed_minions, &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got: Vec<(String, String)> = results
+            .into_iter()
+            .map(|result| (result.host, result.retcode.to_string()))
+            .collect();
+
+        let expected = vec![
+            ("minion1".to_string(), "Success".to_string()),
+            ("minion2".to_string(), "Failure (not connected)".to_string()),
+            (
+                "minion3".to_string(),
+                "Failure (did not respond)".to_string(),
+            ),
+            ("minion4".to_string(), "Failure (not connected)".to_string()),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn user_patterns() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/failure_patterns.yaml"
+        ));
+        let patterns = match read_failure_patterns(path) {
+            Ok(p) => p,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let input = "Salt request timed out for minion1\nminion2:\n    Minion is still booting\n";
+        let (input, got) = cleanup_input_data(input, &patterns);
+
+        let mut expected = DataMap::new();
+        expected.insert(
+            "minion1".to_string(),
+            (
+                FailureCategory::NotResponded,
+                "Salt request timed out.".to_string(),
+            ),
+        );
+        expected.insert(
+            "minion2".to_string(),
+            (
+                FailureCategory::NotConnected,
+                "Minion is still booting".to_string(),
+            ),
+        );
+
+        assert_eq!(input.trim(), "");
+        assert_eq!(got, expected);
+    }
+}
+
+mod test_rewrite {
+    use crate::{
+        get_compressed,
+        get_results,
+        read_rewrites,
+        CompressOptions,
+        MinionResult,
+        ResultOptions,
+        Rewrite,
+    };
+    use serde_json::Value;
+    use std::{
+        collections::BTreeMap as DataMap,
+        path::Path,
+    };
+
+    #[test]
+    fn already_running() {
+        let input = include_str!("../testdata/highstate_is_already_running.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let options = CompressOptions {
+            rewrites: Rewrite::volatile(),
+            ..CompressOptions::default()
+        };
+        let compressed = get_compressed(results, &options);
+        assert_eq!(compressed.len(), 1);
+
+        let (key, results) = compressed.into_iter().next().unwrap();
+        assert_eq!(
+            key.result.unwrap(),
+            "The function \"state.highstate\" is running as PID <pid> and was started at \
+             <timestamp> with jid <jid>"
+        );
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn volatile_values() {
+        let mut result = MinionResult {
+            result: Some(
+                "Downloaded /tmp/tmpa1b2c3 from 10.0.0.1 in 1.5 seconds at 2020-01-02T03:04:05Z"
+                    .to_string(),
+            ),
+            output: Some("id: 123e4567-e89b-12d3-a456-426614174000\n".to_string()),
+            ..MinionResult::default()
+        };
+
+        let got = result.rewrite(&Rewrite::volatile());
+        let expected = vec![
+            "123e4567-e89b-12d3-a456-426614174000",
+            "2020-01-02T03:04:05Z",
+            "10.0.0.1",
+            "1.5 seconds",
+            "/tmp/tmpa1b2c3",
+        ];
+
+        assert_eq!(got, expected);
+        assert_eq!(
+            result.result.unwrap(),
+            "Downloaded <tmp> from <ip> in <duration> at <timestamp>"
+        );
+        assert_eq!(result.output.unwrap(), "id: <uuid>\n");
+    }
+
+    #[test]
+    fn versions_and_settings() {
+        let mut result = MinionResult {
+            result: Some("Updated timeout to 30s from 5s".to_string()),
+            output: Some("openssl:\n  new: 1.1.1.20\n  old: 1.1.1.19\n".to_string()),
+            ..MinionResult::default()
+        };
+
+        let got = result.rewrite(&Rewrite::volatile());
+
+        assert!(got.is_empty());
+        assert_eq!(result.result.unwrap(), "Updated timeout to 30s from 5s");
+        assert_eq!(
+            result.output.unwrap(),
+            "openssl:\n  new: 1.1.1.20\n  old: 1.1.1.19\n"
+        );
+    }
+
+    #[test]
+    fn user_rewrites() {
+        let input = include_str!("../testdata/certificates.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/rewrites.yaml"
+        ));
+        let rewrites = match read_rewrites(path) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let options = CompressOptions {
+            rewrites,
+            ..CompressOptions::default()
+        };
+        let compressed = get_compressed(results, &options);
+        assert_eq!(compressed.len(), 1);
+
+        let (key, results) = compressed.into_iter().next().unwrap();
+        assert_eq!(
+            key.result_text(),
+            Some("Certificate /etc/ssl/web.crt is valid for build-<number>")
+        );
+        assert_eq!(key.output_text(), Some("serial: <serial>\n"));
+
+        // the original values stay in the results
+        let got: Vec<Option<&str>> = results.iter().map(MinionResult::output_text).collect();
+        let expected = vec![Some("serial: 1A:2B:3C\n"), Some("serial: 4D:5E:6F\n")];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn only_in_fields() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/rewrites.yaml"
+        ));
+        let rewrites = match read_rewrites(path) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let mut result = MinionResult {
+            command: Some("serial: 1A".to_string()),
+            result: Some("serial: 1A".to_string()),
+            output: Some("serial: 1A".to_string()),
+            ..MinionResult::default()
+        };
+        result.rewrite(&rewrites);
+
+        assert_eq!(result.command.unwrap(), "serial: 1A");
+        assert_eq!(result.result.unwrap(), "serial: 1A");
+        assert_eq!(result.output.unwrap(), "serial: <serial>");
+    }
+}
+
+mod test_substitute_minion {
+    use crate::{
+        get_compressed,
+        get_results,
+        replace_name,
+        CompressOptions,
+        MinionResult,
+        ResultOptions,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn server_name() {
+        let input = include_str!("../testdata/server_name.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let options = CompressOptions {
+            substitute_minion: true,
+            ..CompressOptions::default()
+        };
+        let compressed = get_compressed(results, &options);
+        assert_eq!(compressed.len(), 1);
+
+        let (key, results) = compressed.into_iter().next().unwrap();
+        assert_eq!(
+            key.output_text(),
+            Some(
+                "---\n+++\n@@ -1,1 +1,1 @@\n-server_name localhost;\n+server_name {{minion}} \
+                 {{minion}};\n"
+            )
+        );
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn only_whole_names() {
+        assert_eq!(
+            replace_name("web1 web12 web1.dc1 my-web1 web1_a", "web1", "{{minion}}"),
+            "{{minion}} web12 {{minion}}.dc1 my-web1 web1_a"
+        );
+    }
+
+    #[test]
+    fn only_dotted_domains() {
+        let substitute = |host: &str, output: &str| {
+            let mut result = MinionResult {
+                host: host.to_string(),
+                output: Some(output.to_string()),
+                ..MinionResult::default()
+            };
+            result.substitute_minion();
+            result.output.unwrap()
+        };
+
+        assert_eq!(
+            substitute("web12.local", "/usr/local/etc/nginx.conf web12.local"),
+            "/usr/local/etc/nginx.conf {{minion}}"
+        );
+        assert_eq!(
+            substitute(
+                "web1.dc1.example.com",
+                "db1.dc1.example.com /srv/dc1.example.com db1.dc1.example.com.au"
+            ),
+            "db1.{{domain}} /srv/dc1.example.com db1.dc1.example.com.au"
+        );
+    }
+}
+
+mod test_cluster {
+    use crate::{
+        get_clusters,
+        get_compressed,
+        get_results,
+        CompressOptions,
+        MinionResult,
+        MinionResults,
+        ResultOptions,
+        Variant,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    fn compressed() -> DataMap<MinionResult, MinionResults> {
+        let input = include_str!("../testdata/similar_outputs.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        get_compressed(results, &CompressOptions::default())
+    }
+
+    #[test]
+    fn similar_outputs() {
+        let (clustered, variants) = get_clusters(compressed(), 0.8);
+        assert_eq!(clustered.len(), 1);
+
+        let (key, results) = clustered.into_iter().next().unwrap();
+        assert!(key.output_text().unwrap().contains("+worker_processes 4;"));
+        assert_eq!(results.len(), 4);
+
+        let expected = vec![Variant {
+            hosts: vec!["web4".to_string()],
+            removed: vec!["+worker_processes 4;".to_string()],
+            added: vec!["+worker_processes 8;".to_string()],
+        }];
+
+        assert_eq!(variants.into_values().collect::<Vec<_>>(), vec![expected]);
+    }
+
+    #[test]
+    fn not_similar_enough() {
+        let (clustered, variants) = get_clusters(compressed(), 0.95);
+
+        assert_eq!(clustered.len(), 2);
+        assert!(variants.is_empty());
+    }
+}
+
+mod test_fingerprint {
+    use super::{
+        filter,
+        hosts,
+    };
+    use crate::{
+        get_compressed,
+        get_filtered,
+        get_fingerprints,
+        get_results,
+        CompressOptions,
+        Filter,
+        ResultOptions,
+    };
+    use regex::Regex;
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    #[test]
+    fn majority_first() {
+        let input = include_str!("../testdata/similar_outputs.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let compressed = get_compressed(results, &CompressOptions::default());
+        let got: Vec<Vec<String>> = get_fingerprints(&compressed)
+            .into_iter()
+            .map(|(_, hosts)| hosts)
+            .collect();
+
+        let expected = vec![
+            vec!["web1".to_string(), "web2".to_string(), "web3".to_string()],
+            vec!["web4".to_string()],
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn all_results_of_host() {
+        let value = serde_json::json!({
+            "minion1": { "retcode": 0, "ret": { "a": "1", "b": "2" } },
+            "minion2": { "retcode": 0, "ret": { "a": "1", "b": "2" } },
+            "minion3": { "retcode": 0, "ret": { "a": "1" } },
+        });
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let compressed = get_compressed(results, &CompressOptions::default());
+        let got: Vec<(usize, Vec<String>)> = get_fingerprints(&compressed)
+            .into_iter()
+            .map(|(keys, hosts)| (keys.len(), hosts))
+            .collect();
+
+        let expected = vec![
+            (2, vec!["minion1".to_string(), "minion2".to_string()]),
+            (1, vec!["minion3".to_string()]),
+        ];
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn filtered_results() {
+        let value = serde_json::json!({
+            "minion1": { "retcode": 0, "ret": { "a": "1", "b": "2" } },
+            "minion2": { "retcode": 0, "ret": { "a": "1", "b": "3" } },
+        });
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let filter = Filter {
+            command: Regex::new("^a$").unwrap(),
+            ..filter()
+        };
+
+        let compressed = get_compressed(results, &CompressOptions::default());
+        let (compressed, removed) = get_filtered(compressed, &filter);
+        let got: Vec<(usize, Vec<String>)> = get_fingerprints(&compressed)
+            .into_iter()
+            .map(|(keys, hosts)| (keys.len(), hosts))
+            .collect();
+
+        assert_eq!(got, vec![(1, hosts(&["minion1", "minion2"]))]);
+        assert_eq!(removed.len(), 2);
+    }
+}
+
+mod test_hosts_display {
+    use super::hosts;
+    use crate::{
+        format_hosts,
+        HostsDisplay,
+    };
+
+    #[test]
+    fn ranged() {
+        let mut input = Vec::new();
+        for number in (1..=40).chain(vec![43]) {
+            input.push(format!("web{:02}.dc1.example.com", number));
+        }
+        input.push("web05.dc2.example.com".to_string());
+        input.push("db1".to_string());
+        input.push("loadbalancer".to_string());
+
+        assert_eq!(
+            format_hosts(&input, HostsDisplay::Ranged),
+            "db1, loadbalancer, web[01-40,43].dc1.example.com, web05.dc2.example.com"
+        );
+    }
+
+    #[test]
+    fn natural_order() {
+        let input = hosts(&["web10", "web9", "web1", "web2"]);
+
+        assert_eq!(
+            format_hosts(&input, HostsDisplay::Full),
+            "web1, web2, web9, web10"
+        );
+        assert_eq!(format_hosts(&input, HostsDisplay::Ranged), "web[1-2,9-10]");
+        assert_eq!(format_hosts(&input, HostsDisplay::Count), "4 hosts");
+    }
+
+    #[test]
+    fn padding() {
+        let input = hosts(&["node08", "node09", "node10", "node100", "node99"]);
+
+        assert_eq!(
+            format_hosts(&input, HostsDisplay::Ranged),
+            "node[08-10,99-100]"
+        );
+    }
+}
+
+mod test_inventory {
+    use super::hosts;
+    use crate::{
+        annotate_hosts,
+        read_csv_inventory,
+        read_inventory,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn csv() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/inventory.csv"
+        ));
+        let inventory = match read_inventory(path) {
+            Ok(i) => i,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert_eq!(inventory.len(), 3);
+        assert_eq!(
+            inventory["loadbalancer_datacenter_sfo"]["environment"],
+            "staging"
+        );
+
+        let got = annotate_hosts(
+            &hosts(&[
+                "loadbalancer_datacenter_dca",
+                "loadbalancer_datacenter_hkg",
+                "loadbalancer_datacenter_sfo",
+                "other",
+            ]),
+            &inventory,
+            &hosts(&["environment"]),
+        );
+
+        assert_eq!(
+            got,
+            vec!["environment=production(2), environment=staging(1), environment=unknown(1)"]
+        );
+    }
+
+    #[test]
+    fn roster() {
+        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/roster.yaml"));
+        let inventory = match read_inventory(path) {
+            Ok(i) => i,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let got = annotate_hosts(
+            &hosts(&["web1", "web2", "web3", "web4"]),
+            &inventory,
+            &hosts(&["role", "datacenter"]),
+        );
+
+        assert_eq!(got, vec!["role=db: hkg(1)", "role=web: dca(2), hkg(1)"]);
+    }
+
+    #[test]
+    fn csv_quoted_fields() {
+        let data = "id,location,owner\nweb1,\"dc1, rack 4\",\"team \"\"ops\"\"\"\n";
+        let inventory = match read_csv_inventory(data) {
+            Ok(i) => i,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert_eq!(inventory["web1"]["location"], "dc1, rack 4");
+        assert_eq!(inventory["web1"]["owner"], "team \"ops\"");
+    }
+}
+
+mod test_expected_minions {
+    use crate::{
+        add_missing_minions,
+        read_expected_minions,
+        ConfigError,
+        FailureCategory,
+        MinionResult,
+        Retcode,
+    };
+    use std::{
+        collections::BTreeSet as DataSet,
+        path::Path,
+    };
+
+    fn read(file: &str) -> DataSet<String> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(file);
+
+        match read_expected_minions(&path) {
+            Ok(m) => m,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    fn minions(minions: &[&str]) -> DataSet<String> {
+        minions.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            read("salt_key.json"),
+            minions(&["web1", "web2", "web3", "web4", "web5"])
+        );
+        assert_eq!(
+            read("roster.yaml"),
+            minions(&["web1", "web2", "web3", "web4"])
+        );
+        assert_eq!(
+            read("expected_minions.txt"),
+            minions(&["web1", "web2", "web5"])
+        );
+    }
+
+    #[test]
+    fn invalid_yaml() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join("invalid_roster.yaml");
+
+        match read_expected_minions(&path) {
+            Err(ConfigError::InvalidYaml(_)) => (),
+            Ok(m) => panic!("unexpected minions: {:?}", m),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn missing() {
+        let mut results = vec![MinionResult {
+            host: "web1".to_string(),
+            retcode: Retcode::Success,
+            ..MinionResult::default()
+        }];
+
+        add_missing_minions(&mut results, &minions(&["web1", "web2"]));
+
+        let got: Vec<(String, Retcode)> = results
+            .into_iter()
+            .map(|result| (result.host, result.retcode))
+            .collect();
+
+        let expected = vec![
+            ("web1".to_string(), Retcode::Success),
+            (
+                "web2".to_string(),
+                Retcode::Failure(None, FailureCategory::Missing),
+            ),
+        ];
+
+        assert_eq!(got, expected);
+    }
+}
+
+mod test_order {
+    use crate::{
+        get_compressed,
+        get_results,
+        sort_compressed,
+        CompressOptions,
+        MinionResult,
+        MinionResults,
+        Order,
+        ResultOptions,
+    };
+    use serde_json::Value;
+    use std::collections::BTreeMap as DataMap;
+
+    fn sorted(order: Order) -> Vec<(String, usize)> {
+        let input = include_str!("../testdata/execution_order.json");
+        let value: Value = serde_json::from_str(input).unwrap();
+
+        let results = match get_results(&value, DataMap::default(), &ResultOptions::default()) {
+            Ok(r) => r,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        let mut entries: Vec<(MinionResult, MinionResults)> =
+            get_compressed(results, &CompressOptions::default())
+                .into_iter()
+                .collect();
+        sort_compressed(&mut entries, order);
+
+        entries
+            .into_iter()
+            .map(|(result, results)| (result.command.unwrap_or_default(), results.len()))
+            .collect()
+    }
+
+    fn commands(entries: &[(String, usize)]) -> Vec<&str> {
+        let mut commands: Vec<&str> = entries.iter().map(|(c, _)| c.as_str()).collect();
+        commands.dedup();
+        commands
+    }
+
+    #[test]
+    fn execution() {
+        let expected = vec![
+            "pkg_|-nginx_|-nginx_|-installed",
+            "file_|-config_|-/etc/nginx/nginx.conf_|-managed",
+            "service_|-nginx_|-nginx_|-running",
+            "user_|-admin_|-admin_|-present",
+            "test_|-check_|-check_|-nop",
+            "cmd_|-reload_|-nginx -s reload_|-run",
+        ];
+
+        assert_eq!(commands(&sorted(Order::Auto)), expected);
+        assert_eq!(commands(&sorted(Order::Execution)), expected);
+    }
+
+    #[test]
+    fn sls() {
+        // grouped by sls file in the order of their run numbers and the state
+        // without a sls file comes last
+        let expected = vec![
+            "pkg_|-nginx_|-nginx_|-installed",
+            "service_|-nginx_|-nginx_|-running",
+            "file_|-config_|-/etc/nginx/nginx.conf_|-managed",
+            "cmd_|-reload_|-nginx -s reload_|-run",
+            "user_|-admin_|-admin_|-present",
+            "test_|-check_|-check_|-nop",
+        ];
+
+        assert_eq!(commands(&sorted(Order::Sls)), expected);
+    }
+
+    #[test]
+    fn failures_and_hosts() {
+        let failures = sorted(Order::Failures);
+        assert_eq!(
+            failures[0],
+            ("service_|-nginx_|-nginx_|-running".to_string(), 1)
+        );
+
+        let hosts = sorted(Order::Hosts);
+        assert!(hosts.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+}
@@ -0,0 +1 @@
+{"service_|-nginx_|-nginx_|-running": {"__id__": "nginx", "__run_num__": 0, "changes": {}, "comment": "The service nginx is already running", "name": "nginx", "result": true}}
@@ -0,0 +1 @@
+"pong"
\ No newline at end of file
@@ -0,0 +1 @@
+funtest.pingargtgt*tgt_typeglobuserrootjid20170719112904786325
\ No newline at end of file
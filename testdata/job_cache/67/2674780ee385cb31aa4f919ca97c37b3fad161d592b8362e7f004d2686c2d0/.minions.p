@@ -0,0 +1 @@
+minion1minion2minion3
\ No newline at end of file
@@ -0,0 +1 @@
+{"return": true, "retcode": 0, "success": true}
\ No newline at end of file
@@ -0,0 +1 @@
+return§retcodesuccess
\ No newline at end of file
@@ -0,0 +1 @@
+{"return": "other job", "retcode": 0, "success": true}
\ No newline at end of file